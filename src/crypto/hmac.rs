@@ -1,4 +1,5 @@
-//! Full implementation of HMAC-SHA1 in accordance with [RFC 2104](http://tools.ietf.org/html/rfc2104)
+//! Full implementation of HMAC-SHA1 and HMAC-SHA256 in accordance with
+//! [RFC 2104](http://tools.ietf.org/html/rfc2104)
 //!
 //!# Examples
 //!
@@ -7,10 +8,12 @@
 //!let key = "key".as_bytes();
 //!let msg = "The quick brown fox jumps over the lazy dog".as_bytes();
 //!let hmac = hmac::hmac_sha1(msg, key);
+//!let hmac = hmac::hmac_sha256(msg, key);
 //!```
 
 use std::ops::BitXor;
 use super::sha1;
+use super::sha256;
 
 // HMAC constants defined in RFC 2104
 
@@ -62,9 +65,36 @@ pub fn hmac_sha1(msg : &[u8], key : &[u8]) -> [u8; 20] {
 
 }
 
+/// Generate the hmac with SHA-256, whose block size is the same as SHA-1's
+#[stable]
+pub fn hmac_sha256(msg : &[u8], key : &[u8]) -> [u8; 32] {
+    let mut key_new : [u8; BLOCKSIZE] = [0u8; BLOCKSIZE];
+
+    if key.len() > BLOCKSIZE {
+        let hash = sha256::sha256(key);
+        for x in range(0, hash.len()) {
+            key_new[x] = hash[x];
+        }
+    } else {
+        for x in range(0, key.len()) {
+            key_new[x] = key[x];
+        }
+    }
+    let mut v = Vec::new();
+    let U8BLOCK(temp) = U8BLOCK(key_new) ^ IPAD;
+    v.push_all(&temp);
+    v.push_all(msg);
+    let temp2 : [u8; 32] = sha256::sha256(&v);
+    v = Vec::new();
+    let U8BLOCK(temp) = U8BLOCK(key_new) ^ OPAD;
+    v.push_all(&temp);
+    v.push_all(&temp2);
+    sha256::sha256(&v)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::hmac_sha1;
+    use super::{hmac_sha1, hmac_sha256};
 
     #[test]
     fn hmac_test1() {
@@ -90,4 +120,30 @@ mod tests {
              0x63u8, 0x70u8, 0x69u8, 0x0eu8, 0x1du8])
     }
 
+    /// [RFC 4231](http://tools.ietf.org/html/rfc4231) test case 2
+    #[test]
+    fn hmac_sha256_test1() {
+        let key = "Jefe".as_bytes();
+        let msg = "what do ya want for nothing?".as_bytes();
+        let h = hmac_sha256(msg, key);
+        assert_eq!(h,
+            [0x5bu8, 0xdcu8, 0xc1u8, 0x46u8, 0xbfu8, 0x60u8, 0x75u8, 0x4eu8,
+             0x6au8, 0x04u8, 0x24u8, 0x26u8, 0x08u8, 0x95u8, 0x75u8, 0xc7u8,
+             0x5au8, 0x00u8, 0x3fu8, 0x08u8, 0x9du8, 0x27u8, 0x39u8, 0x83u8,
+             0x9du8, 0xecu8, 0x58u8, 0xb9u8, 0x64u8, 0xecu8, 0x38u8, 0x43u8]);
+    }
+
+    /// Key longer than a block, which is hashed first
+    #[test]
+    fn hmac_sha256_test2() {
+        let key = [0x6bu8; 100];
+        let msg = "msg".as_bytes();
+        let h = hmac_sha256(msg, &key);
+        assert_eq!(h,
+            [0xbdu8, 0x56u8, 0xa1u8, 0x78u8, 0x2cu8, 0x28u8, 0x30u8, 0xe8u8,
+             0xabu8, 0xc6u8, 0xedu8, 0x86u8, 0x6au8, 0x57u8, 0xa1u8, 0x23u8,
+             0x06u8, 0x61u8, 0xe6u8, 0x50u8, 0xb8u8, 0x4cu8, 0x62u8, 0xf7u8,
+             0xeeu8, 0x3au8, 0xccu8, 0xc5u8, 0xfau8, 0x5au8, 0xf4u8, 0x91u8]);
+    }
+
 }
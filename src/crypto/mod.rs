@@ -4,10 +4,14 @@
 extern crate "rustc-serialize" as serialize;
 use std::fmt;
 use std::default::Default;
+use error::Error;
 
 #[unstable]
 pub mod sha1;
 
+#[unstable]
+pub mod sha256;
+
 pub mod rsa;
 
 #[unstable]
@@ -25,12 +29,14 @@ impl CircularShift for u32 {
     }
 }
 
-#[derive(Copy, Debug, PartialEq, Eq, Clone)]
+#[derive(Copy, Debug, PartialEq, Eq, Clone, RustcEncodable, RustcDecodable)]
 #[unstable]
 /// Signature Type
 pub enum SignatureMethod {
     /// HMACSHA1
     HMACSHA1,
+    /// HMACSHA256, not part of RFC 5849 but required by some providers
+    HMACSHA256,
     /// RSASHA1
     RSASHA1,
     /// Plaintext
@@ -42,6 +48,7 @@ impl fmt::Display for SignatureMethod {
     fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result{
         let out = match *self {
             SignatureMethod::HMACSHA1 => "HMAC-SHA1",
+            SignatureMethod::HMACSHA256 => "HMAC-SHA256",
             SignatureMethod::RSASHA1  => "RSA-SHA1",
             SignatureMethod::PLAINTEXT => "PLAINTEXT"
         };
@@ -57,16 +64,22 @@ impl Default for SignatureMethod {
 
 impl SignatureMethod {
     /// Signs a message with the given signature method
-    pub fn sign(&self, msg: String, key: String) -> String {
+    pub fn sign(&self, msg: String, key: String) -> Result<String, Error> {
         use self::serialize::base64::{self, ToBase64};
         match *self {
             SignatureMethod::HMACSHA1 => {
-                hmac::hmac_sha1(msg.as_bytes(), key.as_bytes())
-                               .as_slice()
-                               .to_base64(base64::STANDARD)
+                Ok(hmac::hmac_sha1(msg.as_bytes(), key.as_bytes())
+                                  .as_slice()
+                                  .to_base64(base64::STANDARD))
+            },
+            SignatureMethod::HMACSHA256 => {
+                Ok(hmac::hmac_sha256(msg.as_bytes(), key.as_bytes())
+                                    .as_slice()
+                                    .to_base64(base64::STANDARD))
             },
-            SignatureMethod::RSASHA1  => String::from_str("RSASHA"),
-            SignatureMethod::PLAINTEXT => String::from_str("PLAINTEXT")
+            SignatureMethod::RSASHA1  => Err(Error::UnsupportedSignatureMethod(*self)),
+            // RFC 5849 3.4.4: the signature is the key itself
+            SignatureMethod::PLAINTEXT => Ok(key)
         }
     }
 }
@@ -0,0 +1,154 @@
+//! Full implementation of the SHA-256 hash algorithm in accordance with
+//! [RFC 6234](https://tools.ietf.org/html/rfc6234)
+//!
+//!# Examples
+//!
+//!```rust
+//! use rust_oauth::crypto::sha256::sha256;
+//! let msg = "The quick brown fox jumped over the lazy dog".as_bytes();
+//! let hash = sha256(msg);
+//!```
+//!
+
+use super::CircularShift;
+
+const K : [u32; 64] = [
+    0x428a2f98u32, 0x71374491u32, 0xb5c0fbcfu32, 0xe9b5dba5u32, 0x3956c25bu32, 0x59f111f1u32, 0x923f82a4u32, 0xab1c5ed5u32,
+    0xd807aa98u32, 0x12835b01u32, 0x243185beu32, 0x550c7dc3u32, 0x72be5d74u32, 0x80deb1feu32, 0x9bdc06a7u32, 0xc19bf174u32,
+    0xe49b69c1u32, 0xefbe4786u32, 0x0fc19dc6u32, 0x240ca1ccu32, 0x2de92c6fu32, 0x4a7484aau32, 0x5cb0a9dcu32, 0x76f988dau32,
+    0x983e5152u32, 0xa831c66du32, 0xb00327c8u32, 0xbf597fc7u32, 0xc6e00bf3u32, 0xd5a79147u32, 0x06ca6351u32, 0x14292967u32,
+    0x27b70a85u32, 0x2e1b2138u32, 0x4d2c6dfcu32, 0x53380d13u32, 0x650a7354u32, 0x766a0abbu32, 0x81c2c92eu32, 0x92722c85u32,
+    0xa2bfe8a1u32, 0xa81a664bu32, 0xc24b8b70u32, 0xc76c51a3u32, 0xd192e819u32, 0xd6990624u32, 0xf40e3585u32, 0x106aa070u32,
+    0x19a4c116u32, 0x1e376c08u32, 0x2748774cu32, 0x34b0bcb5u32, 0x391c0cb3u32, 0x4ed8aa4au32, 0x5b9cca4fu32, 0x682e6ff3u32,
+    0x748f82eeu32, 0x78a5636fu32, 0x84c87814u32, 0x8cc70208u32, 0x90befffau32, 0xa4506cebu32, 0xbef9a3f7u32, 0xc67178f2u32];
+const H_INIT : [u32; 8] =
+    [0x6a09e667u32, 0xbb67ae85u32, 0x3c6ef372u32, 0xa54ff53au32,
+     0x510e527fu32, 0x9b05688cu32, 0x1f83d9abu32, 0x5be0cd19u32];
+
+/// Create a hash of the input data `msg`.
+#[stable]
+pub fn sha256(msg : &[u8]) -> [u8; 32] {
+    let mut h : [u32; 8] = H_INIT;
+
+    // the message is followed by a single 1 bit, zeros up to the last 8 bytes of a
+    // block, and its length in bits
+    let mut padded = msg.to_vec();
+    padded.push(0x80u8);
+    while padded.len() % 64 != 56 {
+        padded.push(0u8);
+    }
+    let len = (msg.len() as u64) * 8;
+    for i in range(0, 8) {
+        padded.push((len >> (56 - 8 * i)) as u8);
+    }
+
+    for block in padded.chunks(64) {
+        digest_block(block, &mut h);
+    }
+
+    let mut res : [u8; 32] = [0u8; 32];
+    for i in range(0, 8) {
+        res[4*i] =   ((h[i] & 0xFF000000) >> 24) as u8;
+        res[4*i+1] = ((h[i] & 0x00FF0000) >> 16) as u8;
+        res[4*i+2] = ((h[i] & 0x0000FF00) >> 8) as u8;
+        res[4*i+3] =  (h[i] & 0x000000FF) as u8;
+    }
+    res
+}
+
+/// Processes a 64 byte `block`. Rotations to the right by `n` bits are written as
+/// rotations to the left by `32 - n`
+#[stable]
+fn digest_block(block : &[u8], h : &mut [u32; 8]) {
+    let mut w : [u32; 64] = [0u32; 64];
+
+    for i in range(0, 16) {
+        w[i] = ((block[4*i] as u32) << 24) | ((block[4*i + 1] as u32) << 16)
+                | ((block[4*i+2] as u32) << 8) | block[4*i+3] as u32;
+    }
+
+    for t in range(16, 64) {
+        let s0 = w[t - 15].circular_shift(25) ^ w[t - 15].circular_shift(14) ^ (w[t - 15] >> 3);
+        let s1 = w[t - 2].circular_shift(15) ^ w[t - 2].circular_shift(13) ^ (w[t - 2] >> 10);
+        w[t] = w[t - 16].wrapping_add(s0).wrapping_add(w[t - 7]).wrapping_add(s1);
+    }
+
+    let mut a = h[0];
+    let mut b = h[1];
+    let mut c = h[2];
+    let mut d = h[3];
+    let mut e = h[4];
+    let mut f = h[5];
+    let mut g = h[6];
+    let mut hh = h[7];
+
+    for t in range(0, 64) {
+        let s1 = e.circular_shift(26) ^ e.circular_shift(21) ^ e.circular_shift(7);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[t]).wrapping_add(w[t]);
+        let s0 = a.circular_shift(30) ^ a.circular_shift(19) ^ a.circular_shift(10);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+    h[0] = h[0].wrapping_add(a);
+    h[1] = h[1].wrapping_add(b);
+    h[2] = h[2].wrapping_add(c);
+    h[3] = h[3].wrapping_add(d);
+    h[4] = h[4].wrapping_add(e);
+    h[5] = h[5].wrapping_add(f);
+    h[6] = h[6].wrapping_add(g);
+    h[7] = h[7].wrapping_add(hh);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sha256};
+
+    /// Classic test
+    #[test]
+    fn sha256_test1(){
+        let test = "abc".as_bytes();
+        assert_eq!(sha256(test), [0xbau8, 0x78u8, 0x16u8, 0xbfu8, 0x8fu8, 0x01u8, 0xcfu8, 0xeau8,
+                                 0x41u8, 0x41u8, 0x40u8, 0xdeu8, 0x5du8, 0xaeu8, 0x22u8, 0x23u8,
+                                 0xb0u8, 0x03u8, 0x61u8, 0xa3u8, 0x96u8, 0x17u8, 0x7au8, 0x9cu8,
+                                 0xb4u8, 0x10u8, 0xffu8, 0x61u8, 0xf2u8, 0x00u8, 0x15u8, 0xadu8])
+    }
+
+    /// Empty input
+    #[test]
+    fn sha256_test2(){
+        let test = "".as_bytes();
+        assert_eq!(sha256(test), [0xe3u8, 0xb0u8, 0xc4u8, 0x42u8, 0x98u8, 0xfcu8, 0x1cu8, 0x14u8,
+                                 0x9au8, 0xfbu8, 0xf4u8, 0xc8u8, 0x99u8, 0x6fu8, 0xb9u8, 0x24u8,
+                                 0x27u8, 0xaeu8, 0x41u8, 0xe4u8, 0x64u8, 0x9bu8, 0x93u8, 0x4cu8,
+                                 0xa4u8, 0x95u8, 0x99u8, 0x1bu8, 0x78u8, 0x52u8, 0xb8u8, 0x55u8])
+    }
+
+    /// Test of multi block input
+    #[test]
+    fn sha256_test3(){
+        let test = "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".as_bytes();
+        assert_eq!(sha256(test), [0x24u8, 0x8du8, 0x6au8, 0x61u8, 0xd2u8, 0x06u8, 0x38u8, 0xb8u8,
+                                 0xe5u8, 0xc0u8, 0x26u8, 0x93u8, 0x0cu8, 0x3eu8, 0x60u8, 0x39u8,
+                                 0xa3u8, 0x3cu8, 0xe4u8, 0x59u8, 0x64u8, 0xffu8, 0x21u8, 0x67u8,
+                                 0xf6u8, 0xecu8, 0xedu8, 0xd4u8, 0x19u8, 0xdbu8, 0x06u8, 0xc1u8])
+    }
+
+    /// Test of 512 bit input
+    #[test]
+    fn sha256_test4(){
+        let test = "XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX".as_bytes();
+        assert_eq!(sha256(test), [0xbeu8, 0x4du8, 0x3eu8, 0xd1u8, 0xfcu8, 0x5fu8, 0x5cu8, 0xfau8,
+                                 0x00u8, 0x73u8, 0x5bu8, 0x28u8, 0x60u8, 0x5au8, 0x97u8, 0x5fu8,
+                                 0x32u8, 0x93u8, 0xfau8, 0x23u8, 0x5du8, 0x23u8, 0xabu8, 0xb1u8,
+                                 0xd9u8, 0xdeu8, 0xa3u8, 0x80u8, 0xfbu8, 0xa0u8, 0xfbu8, 0x87u8])
+    }
+}
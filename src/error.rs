@@ -0,0 +1,83 @@
+//! Error type shared by every fallible operation in the crate
+
+use std::error::{Error as StdError, FromError};
+use std::fmt;
+use crypto::SignatureMethod;
+use oauth1::client::Transmission;
+use oauth1::client::problem::Problem;
+use oauth1::client::response::ResponseError;
+
+/// Everything that can go wrong while signing or sending an OAuth request
+#[derive(Debug)]
+pub enum Error {
+    /// The request URL could not be parsed or used in a base string
+    InvalidUrl(String),
+    /// The signature method is not supported by this crate, as RSA-SHA1 is not yet
+    UnsupportedSignatureMethod(SignatureMethod),
+    /// The OAuth parameters cannot be placed as the `Transmission` asks for this request
+    UnsupportedTransmission(Transmission),
+    /// The operating system's random number generator was unavailable
+    Randomness(String),
+    /// The http library used to send the request failed
+    Transport(Box<StdError + Send>),
+    /// The provider answered with a response that could not be used, including one
+    /// that is not valid UTF-8
+    Provider(ResponseError),
+    /// No account is stored under the given id
+    UnknownAccount(String),
+    /// The token credentials have no session handle to renew them with
+    NotRenewable,
+    /// An OAuth Echo request named a service provider which is not trusted
+    UntrustedProvider(String),
+    /// The provider rejected the request, and described why with the Problem
+    /// Reporting extension
+    Problem(Problem),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidUrl(ref url) => write!(f, "invalid url `{}`", url),
+            Error::UnsupportedSignatureMethod(ref m) => write!(f, "unsupported signature method {}", m),
+            Error::UnsupportedTransmission(ref t) => write!(f, "unsupported transmission {:?}", t),
+            Error::Randomness(ref msg) => write!(f, "random number generator unavailable: {}", msg),
+            Error::Transport(ref err) => write!(f, "transport error: {}", err),
+            Error::Provider(ref err) => write!(f, "provider error: {}", err),
+            Error::UnknownAccount(ref id) => write!(f, "unknown account `{}`", id),
+            Error::NotRenewable => write!(f, "{}", self.description()),
+            Error::UntrustedProvider(ref url) => write!(f, "untrusted service provider `{}`", url),
+            Error::Problem(ref problem) => write!(f, "provider reported a problem: {}", problem),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::InvalidUrl(_) => "invalid url",
+            Error::UnsupportedSignatureMethod(_) => "unsupported signature method",
+            Error::UnsupportedTransmission(_) => "unsupported transmission",
+            Error::Randomness(_) => "random number generator unavailable",
+            Error::Transport(ref err) => err.description(),
+            Error::Provider(ref err) => err.description(),
+            Error::UnknownAccount(_) => "unknown account",
+            Error::NotRenewable => "token credentials have no session handle",
+            Error::UntrustedProvider(_) => "untrusted service provider",
+            Error::Problem(_) => "provider reported a problem",
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Transport(ref err) => Some(&**err),
+            Error::Provider(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl FromError<ResponseError> for Error {
+    fn from_error(err: ResponseError) -> Error {
+        Error::Provider(err)
+    }
+}
@@ -1,8 +1,16 @@
 //! Implementation of OAuth 1.0 in accordance with [RFC 5849](http://tools.ietf.org/html/rfc5849)
 #![feature(core, collections)]
 
+#[macro_use]
+extern crate log;
+
+extern crate "rustc-serialize" as rustc_serialize;
+
 #[unstable()]
 pub mod crypto;
 
+pub mod error;
+pub use error::Error;
+
 #[unstable()]
 pub mod oauth1;
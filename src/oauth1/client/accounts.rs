@@ -0,0 +1,347 @@
+//! Keeps the token credentials of many end users under a single consumer. Accounts are
+//! identified by an id of your choosing, and kept in an `AccountStore`; `MemoryStore` is
+//! used by default, and other stores can persist accounts elsewhere.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::HTTPMethod;
+//! use rust_oauth::oauth1::client::accounts::AccountManager;
+//! use rust_oauth::oauth1::client::credentials::{ConsumerCredentials, OwnedSession, TokenPair};
+//!
+//! let consumer = OwnedSession::new(ConsumerCredentials::new("key", "secret"), None,
+//!                                  SignatureMethod::HMACSHA1);
+//! let mut manager = AccountManager::new(consumer);
+//! manager.add_account("alice", TokenPair::new("token", "token_secret"));
+//! let signed = manager.sign("alice", HTTPMethod::GET, "https://api.example.com/me", vec![]).unwrap();
+//! assert!(manager.get_account("alice").unwrap().get_last_used().is_some());
+//!```
+
+use oauth1::client::time::{Timespec, get_time};
+use std::collections::HashMap;
+use std::default::Default;
+use oauth1::client::HTTPMethod;
+use oauth1::client::credentials::{OwnedSession, TokenPair};
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::{Response, Transport};
+use oauth1::client::async_transport::{AsyncTransport, Pending};
+use error::Error;
+
+/// The token credentials of one end user, with when they were issued and last used
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Account {
+    token : TokenPair,
+    issued : Timespec,
+    last_used : Option<Timespec>,
+}
+
+impl Account {
+    /// Creates an account for `token`, issued now
+    pub fn new(token: TokenPair) -> Account {
+        Account::issued_at(token, get_time())
+    }
+
+    /// Creates an account for `token`, issued at `issued`, for instance when restoring
+    /// it from storage
+    pub fn issued_at(token: TokenPair, issued: Timespec) -> Account {
+        Account { token : token, issued : issued, last_used : None }
+    }
+
+    pub fn get_token(&self) -> &TokenPair {
+        &self.token
+    }
+
+    pub fn get_issued(&self) -> Timespec {
+        self.issued
+    }
+
+    /// Returns when a request was last signed for this account, if ever
+    pub fn get_last_used(&self) -> Option<Timespec> {
+        self.last_used
+    }
+
+    pub fn set_last_used(&mut self, last_used: Timespec) {
+        self.last_used = Some(last_used);
+    }
+}
+
+/// Where an `AccountManager` keeps its accounts
+pub trait AccountStore {
+    /// Returns the account `id`, if it is stored
+    fn get(&self, id: &str) -> Option<Account>;
+
+    /// Stores `account` as `id`, replacing any previous account with that id
+    fn insert(&mut self, id: &str, account: Account);
+
+    /// Removes the account `id`, returning it if it was stored
+    fn remove(&mut self, id: &str) -> Option<Account>;
+
+    /// Returns the id of every stored account
+    fn ids(&self) -> Vec<String>;
+}
+
+/// Keeps accounts in memory, for the lifetime of the store
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    accounts : HashMap<String, Account>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        Default::default()
+    }
+}
+
+impl AccountStore for MemoryStore {
+    fn get(&self, id: &str) -> Option<Account> {
+        self.accounts.get(id).map(|a| a.clone())
+    }
+
+    fn insert(&mut self, id: &str, account: Account) {
+        self.accounts.insert(id.to_string(), account);
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Account> {
+        self.accounts.remove(id)
+    }
+
+    fn ids(&self) -> Vec<String> {
+        self.accounts.keys().map(|k| k.clone()).collect()
+    }
+}
+
+/// Signs requests for the accounts in `store`, with the consumer credentials and
+/// configuration of `consumer`. Signing a request records it as the account's last use
+pub struct AccountManager<S = MemoryStore> {
+    consumer : OwnedSession,
+    store : S,
+}
+
+impl AccountManager<MemoryStore> {
+    /// Creates a manager keeping its accounts in memory
+    pub fn new(consumer: OwnedSession) -> AccountManager<MemoryStore> {
+        AccountManager::with_store(consumer, MemoryStore::new())
+    }
+}
+
+impl<S: AccountStore> AccountManager<S> {
+    /// Creates a manager keeping its accounts in `store`
+    pub fn with_store(consumer: OwnedSession, store: S) -> AccountManager<S> {
+        AccountManager { consumer : consumer, store : store }
+    }
+
+    /// Adds an account for `token`, issued now, replacing any account with the same id
+    pub fn add_account(&mut self, id: &str, token: TokenPair) {
+        self.store.insert(id, Account::new(token));
+    }
+
+    /// Removes the account `id`, returning it if it existed
+    pub fn remove_account(&mut self, id: &str) -> Option<Account> {
+        self.store.remove(id)
+    }
+
+    pub fn get_account(&self, id: &str) -> Option<Account> {
+        self.store.get(id)
+    }
+
+    /// Returns the id of every account
+    pub fn get_account_ids(&self) -> Vec<String> {
+        self.store.ids()
+    }
+
+    pub fn get_consumer(&self) -> &OwnedSession {
+        &self.consumer
+    }
+
+    pub fn get_store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns an `OwnedSession` signing for the account `id`, which can be kept or
+    /// moved to another thread. Requests signed with it aren't recorded as a use
+    pub fn signer(&self, id: &str) -> Result<OwnedSession, Error> {
+        let account = try!(self.account(id));
+        let mut session = self.consumer.clone();
+        session.set_token(Some(account.token));
+        Ok(session)
+    }
+
+    /// Signs a request for the account `id`, without sending it
+    pub fn sign(&mut self, id: &str, method: HTTPMethod, base_url: &str,
+                data: Vec<(&str, &str)>) -> Result<SignedRequest, Error> {
+        let account = try!(self.account(id));
+        let signed = try!(self.consumer.sign_with_token(&account.token, method, base_url, data));
+        self.record_use(id, account);
+        Ok(signed)
+    }
+
+    /// Signs a request for the account `id` and sends it with `transport`. The account's
+    /// token is renewed first when it is about to expire, if the consumer was set up with
+    /// `OwnedSession::set_auto_renew`. A clock offset learned from the response is kept
+    /// for every account
+    pub fn request<T: Transport>(&mut self, id: &str, transport: &T, method: HTTPMethod,
+                                 base_url: &str, data: Vec<(&str, &str)>) -> Result<Response, Error> {
+        let mut account = try!(self.account(id));
+        let renewed = try!(self.consumer.renew_if_due(&account.token, transport));
+        match renewed {
+            Some(token) => account.token = token,
+            None => ()
+        };
+        let response = self.consumer.request_with_token(&account.token, transport, method, base_url, data);
+        self.record_use(id, account);
+        response
+    }
+
+    /// Signs a request for the account `id` and hands it to `transport`. A token about
+    /// to expire is renewed first, as with `request`, waiting for the provider's answer
+    pub fn request_async<T: AsyncTransport>(&mut self, id: &str, transport: &T, method: HTTPMethod,
+                                            base_url: &str, data: Vec<(&str, &str)>)
+                                            -> Result<Pending<Response>, Error> {
+        let mut account = try!(self.account(id));
+        let renewed = try!(self.consumer.renew_if_due_async(&account.token, transport));
+        match renewed {
+            Some(token) => account.token = token,
+            None => ()
+        };
+        let pending = try!(self.consumer.request_async_with_token(&account.token, transport, method,
+                                                                  base_url, data));
+        self.record_use(id, account);
+        Ok(pending)
+    }
+
+    fn account(&self, id: &str) -> Result<Account, Error> {
+        self.store.get(id).ok_or_else(|| Error::UnknownAccount(id.to_string()))
+    }
+
+    fn record_use(&mut self, id: &str, mut account: Account) {
+        account.set_last_used(get_time());
+        self.store.insert(id, account);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Account, AccountManager, AccountStore, MemoryStore};
+    use oauth1::client::time::Timespec;
+    use oauth1::client::HTTPMethod;
+    use oauth1::client::credentials::{ConsumerCredentials, OwnedSession, TokenPair};
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    fn manager() -> AccountManager {
+        let consumer = OwnedSession::new(ConsumerCredentials::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44"),
+                                         None, SignatureMethod::HMACSHA1);
+        let mut manager = AccountManager::new(consumer);
+        manager.add_account("alice", TokenPair::new("alice_token", "alice_secret"));
+        manager.add_account("bob", TokenPair::new("bob_token", "bob_secret"));
+        manager
+    }
+
+    #[test]
+    fn account_manager_sign() {
+        let mut manager = manager();
+        assert!(manager.get_account("alice").unwrap().get_last_used().is_none());
+        let signed = manager.sign("alice", HTTPMethod::GET, "http://example.com/me", vec![]).unwrap();
+        assert!(signed.get_header("Authorization").unwrap().contains("oauth_token=\"alice_token\""));
+
+        let alice = manager.get_account("alice").unwrap();
+        assert!(alice.get_last_used().unwrap() >= alice.get_issued());
+        assert!(manager.get_account("bob").unwrap().get_last_used().is_none());
+
+        let expected = manager.get_consumer().session_with_token(alice.get_token())
+                              .generate_signature(signed.get_base_string().to_string()).unwrap();
+        assert_eq!(signed.get_signature(), expected.as_slice());
+    }
+
+    #[test]
+    fn account_manager_add_remove() {
+        let mut manager = manager();
+        let mut ids = manager.get_account_ids();
+        ids.sort();
+        assert_eq!(ids, vec!["alice".to_string(), "bob".to_string()]);
+
+        let bob = manager.remove_account("bob").unwrap();
+        assert_eq!(bob.get_token().get_token(), "bob_token");
+        assert!(manager.remove_account("bob").is_none());
+        match manager.sign("bob", HTTPMethod::GET, "http://example.com/me", vec![]) {
+            Err(Error::UnknownAccount(ref id)) => assert_eq!(id.as_slice(), "bob"),
+            _ => panic!("expected Error::UnknownAccount")
+        }
+    }
+
+    #[test]
+    fn account_manager_custom_store() {
+        /// Forgets every account but the latest one
+        struct SingleStore(Option<(String, Account)>);
+
+        impl AccountStore for SingleStore {
+            fn get(&self, id: &str) -> Option<Account> {
+                match self.0 {
+                    Some((ref i, ref a)) if i.as_slice() == id => Some(a.clone()),
+                    _ => None
+                }
+            }
+            fn insert(&mut self, id: &str, account: Account) {
+                self.0 = Some((id.to_string(), account));
+            }
+            fn remove(&mut self, id: &str) -> Option<Account> {
+                match self.get(id) {
+                    Some(a) => { self.0 = None; Some(a) },
+                    None => None
+                }
+            }
+            fn ids(&self) -> Vec<String> {
+                self.0.iter().map(|&(ref i, _)| i.clone()).collect()
+            }
+        }
+
+        let consumer = OwnedSession::new(ConsumerCredentials::new("key", "secret"), None,
+                                         SignatureMethod::PLAINTEXT);
+        let mut manager = AccountManager::with_store(consumer, SingleStore(None));
+        manager.add_account("alice", TokenPair::new("alice_token", "alice_secret"));
+        manager.add_account("bob", TokenPair::new("bob_token", "bob_secret"));
+        assert_eq!(manager.get_account_ids(), vec!["bob".to_string()]);
+        let signed = manager.sign("bob", HTTPMethod::GET, "http://example.com/me", vec![]).unwrap();
+        assert_eq!(signed.get_signature(), "secret&bob_secret");
+    }
+
+    #[test]
+    fn account_manager_auto_renew() {
+        use oauth1::client::signed_request::SignedRequest;
+        use oauth1::client::time::get_time;
+        use oauth1::client::transport::Response;
+        let consumer = OwnedSession::new(ConsumerCredentials::new("key", "secret"), None, SignatureMethod::HMACSHA1)
+                           .set_auto_renew("https://example.com/token", 300);
+        let mut manager = AccountManager::new(consumer);
+        let mut token = TokenPair::new("old", "old_secret");
+        token.set_session_handle(Some("handle"));
+        token.set_expires_at(Some(get_time().sec + 60));
+        manager.add_account("alice", token);
+
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            if req.get_url() == "https://example.com/token" {
+                assert!(req.get_header("Authorization").unwrap().contains("oauth_token=\"old\""));
+                Ok(Response::new(200, vec![], b"oauth_token=renewed&oauth_token_secret=renewed_secret\
+                                               &oauth_expires_in=3600".to_vec()))
+            } else {
+                assert!(req.get_header("Authorization").unwrap().contains("oauth_token=\"renewed\""));
+                Ok(Response::new(200, vec![], vec![]))
+            }
+        };
+        manager.request("alice", &transport, HTTPMethod::GET, "https://example.com/me", vec![]).unwrap();
+        let alice = manager.get_account("alice").unwrap();
+        assert_eq!(alice.get_token().get_token(), "renewed");
+        assert_eq!(alice.get_token().get_session_handle(), Some("handle"));
+    }
+
+    #[test]
+    fn memory_store_restore() {
+        let mut store = MemoryStore::new();
+        let issued = Timespec::new(1318622958, 0);
+        store.insert("alice", Account::issued_at(TokenPair::new("t", "s"), issued));
+        assert_eq!(store.get("alice").unwrap().get_issued(), issued);
+        assert!(store.get("bob").is_none());
+    }
+}
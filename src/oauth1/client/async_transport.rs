@@ -0,0 +1,241 @@
+//! Sends signed requests without blocking the caller.
+//!
+//! An `AsyncTransport` starts sending a `SignedRequest` and returns right away; it
+//! completes the `Reply` it was given once the response arrives, from whichever
+//! thread or event loop it runs on. The caller gets a `Pending` handle it can poll
+//! with `try_wait`, block on with `wait`, or hand a callback to with `on_complete`.
+//! Signing itself stays synchronous and cheap, so nothing here depends on a
+//! particular executor.
+//!
+//!# Example
+//!
+//!```
+//! use std::thread;
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::HTTPMethod;
+//! use rust_oauth::oauth1::client::session::Session;
+//! use rust_oauth::oauth1::client::signed_request::SignedRequest;
+//! use rust_oauth::oauth1::client::transport::Response;
+//! use rust_oauth::oauth1::client::async_transport::Reply;
+//!
+//! let transport = |req: SignedRequest, reply: Reply| {
+//!     thread::spawn(move || {
+//!         reply.complete(Ok(Response::new(200, vec![], req.get_url().as_bytes().to_vec())))
+//!     });
+//! };
+//! let mut s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1);
+//! let pending = s.request_async(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap();
+//! assert_eq!(pending.wait().unwrap().get_status(), 200);
+//!```
+
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::Response;
+use error::Error;
+use std::boxed::FnBox;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{channel, Sender, Receiver, RecvError, TryRecvError};
+
+type Callback = Box<FnBox(Result<Response, Error>) + Send>;
+
+/// Sends a signed request over http without waiting for the response
+pub trait AsyncTransport {
+    /// Starts sending `request` exactly as signed and returns immediately. The
+    /// transport must complete `reply` once, from any thread, with the response or
+    /// an `Error::Transport`
+    fn send_async(&self, request: SignedRequest, reply: Reply);
+}
+
+impl<F> AsyncTransport for F where F: Fn(SignedRequest, Reply) {
+    fn send_async(&self, request: SignedRequest, reply: Reply) {
+        (*self)(request, reply)
+    }
+}
+
+/// Completes a `Pending` request. It can be sent to another thread. Dropping it
+/// without completing it fails the request with `Error::Transport`
+pub struct Reply {
+    sender : Sender<Result<Response, Error>>,
+    callback : Arc<Mutex<Option<Callback>>>,
+    completed : bool,
+}
+
+impl Reply {
+    /// Hands the outcome of the request to the waiting `Pending`, or runs the callback
+    /// given to `Pending::on_complete` on the current thread
+    pub fn complete(mut self, result: Result<Response, Error>) {
+        self.deliver(result)
+    }
+
+    fn deliver(&mut self, result: Result<Response, Error>) {
+        self.completed = true;
+        let callback = {
+            let mut slot = lock(&self.callback);
+            match slot.take() {
+                Some(callback) => callback,
+                None => {
+                    // the receiving side may have been dropped, in which case nobody is interested
+                    let _ = self.sender.send(result);
+                    return
+                }
+            }
+        };
+        callback(result)
+    }
+}
+
+impl Drop for Reply {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.deliver(Err(Error::Transport(Box::new(RecvError))))
+        }
+    }
+}
+
+/// Handle on a request that is being sent by an `AsyncTransport`. `T` is the value
+/// produced from the response, such as parsed credentials
+pub struct Pending<T> {
+    receiver : Receiver<Result<Response, Error>>,
+    callback : Arc<Mutex<Option<Callback>>>,
+    map : fn(Response) -> Result<T, Error>,
+}
+
+impl<T> Pending<T> {
+    /// Creates a pending request, and the `Reply` which completes it. `map` turns
+    /// the response into the value returned by `wait`
+    pub fn new(map: fn(Response) -> Result<T, Error>) -> (Pending<T>, Reply) {
+        let (sender, receiver) = channel();
+        let callback = Arc::new(Mutex::new(None));
+        (Pending { receiver : receiver, callback : callback.clone(), map : map },
+         Reply { sender : sender, callback : callback, completed : false })
+    }
+
+    /// Blocks until the transport completes the request
+    pub fn wait(self) -> Result<T, Error> {
+        match self.receiver.recv() {
+            Ok(result) => result.and_then(self.map),
+            Err(e) => Err(Error::Transport(Box::new(e)))
+        }
+    }
+
+    /// Returns the outcome if the transport already completed the request, or
+    /// `None` if it is still in flight
+    pub fn try_wait(&self) -> Option<Result<T, Error>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result.and_then(self.map)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(Error::Transport(Box::new(RecvError))))
+        }
+    }
+
+    /// Calls `f` with the outcome once the transport completes the request, without
+    /// blocking. `f` runs on the thread completing the `Reply`, or right away on the
+    /// current thread if the request is already completed
+    pub fn on_complete<F>(self, f: F) where F: FnOnce(Result<T, Error>) + Send + 'static, T: 'static {
+        let map = self.map;
+        let callback : Callback = Box::new(move |result: Result<Response, Error>| f(result.and_then(map)));
+        let result = {
+            let mut slot = lock(&self.callback);
+            match self.receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Disconnected) => Err(Error::Transport(Box::new(RecvError))),
+                Err(TryRecvError::Empty) => {
+                    *slot = Some(callback);
+                    return
+                }
+            }
+        };
+        callback(result)
+    }
+}
+
+/// Locks the callback slot. It is only held to swap the callback, never while
+/// running it, so a poisoned lock still holds a consistent value
+fn lock(slot: &Mutex<Option<Callback>>) -> MutexGuard<Option<Callback>> {
+    match slot.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner()
+    }
+}
+
+/// Hands `request` to `transport`, returning the handle on its response
+pub fn send<A: AsyncTransport, T>(transport: &A, request: SignedRequest,
+                                   map: fn(Response) -> Result<T, Error>) -> Pending<T> {
+    let (pending, reply) = Pending::new(map);
+    transport.send_async(request, reply);
+    pending
+}
+
+/// `map` for requests whose response is returned as is
+pub fn identity(response: Response) -> Result<Response, Error> {
+    Ok(response)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Pending, Reply, send, identity};
+    use oauth1::client::{HTTPMethod, Transmission};
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::transport::Response;
+    use error::Error;
+    use std::thread;
+    use std::sync::mpsc::channel;
+
+    fn request() -> SignedRequest {
+        SignedRequest::new(HTTPMethod::GET, "http://example.com/", vec![],
+                           Transmission::Header, None, vec![], String::new())
+    }
+
+    #[test]
+    fn pending_completed_from_another_thread() {
+        let transport = |req: SignedRequest, reply: Reply| {
+            let url = req.get_url().to_string();
+            thread::spawn(move || {
+                reply.complete(Ok(Response::new(200, vec![], url.into_bytes())))
+            });
+        };
+        let pending = send(&transport, request(), identity);
+        assert_eq!(pending.wait().unwrap().get_body_str(), Some("http://example.com/"));
+    }
+
+    #[test]
+    fn pending_try_wait() {
+        let (pending, reply) = Pending::new(identity);
+        assert!(pending.try_wait().is_none());
+        reply.complete(Ok(Response::new(204, vec![], Vec::new())));
+        assert_eq!(pending.try_wait().unwrap().unwrap().get_status(), 204);
+    }
+
+    #[test]
+    fn pending_on_complete() {
+        let (done, received) = channel();
+        let (pending, reply) = Pending::new(identity);
+        pending.on_complete(move |result| done.send(result.unwrap().get_status()).unwrap());
+        thread::spawn(move || reply.complete(Ok(Response::new(200, vec![], Vec::new()))));
+        assert_eq!(received.recv().unwrap(), 200);
+
+        let (done, received) = channel();
+        let (pending, reply) = Pending::new(identity);
+        reply.complete(Ok(Response::new(204, vec![], Vec::new())));
+        pending.on_complete(move |result| done.send(result.unwrap().get_status()).unwrap());
+        assert_eq!(received.recv().unwrap(), 204);
+    }
+
+    #[test]
+    fn pending_on_complete_dropped_reply() {
+        let (done, received) = channel();
+        let (pending, reply) = Pending::new(identity);
+        pending.on_complete(move |result| done.send(result.is_err()).unwrap());
+        drop(reply);
+        assert!(received.recv().unwrap());
+    }
+
+    #[test]
+    fn pending_dropped_reply() {
+        let transport = |_: SignedRequest, reply: Reply| { drop(reply); };
+        match send(&transport, request(), identity).wait() {
+            Err(Error::Transport(_)) => (),
+            _ => panic!("expected Error::Transport")
+        }
+    }
+}
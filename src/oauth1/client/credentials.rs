@@ -0,0 +1,789 @@
+//! Owned credentials and sessions. Unlike `Session`, they don't borrow from the
+//! configuration they were built from, so they can be stored anywhere and moved across
+//! threads. They implement rustc-serialize's `Encodable` and `Decodable`, so token
+//! credentials can be saved after the three-legged flow and restored later. Their
+//! `Debug` output leaves out the secrets, so they can be logged.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::HTTPMethod;
+//! use rust_oauth::oauth1::client::credentials::{ConsumerCredentials, TokenPair, OwnedSession};
+//!
+//! let consumer = ConsumerCredentials::new("key", "secret");
+//! let token = TokenPair::new("token", "token_secret");
+//! let session = OwnedSession::new(consumer, Some(token), SignatureMethod::HMACSHA1);
+//! let signed = session.sign(HTTPMethod::GET, "https://api.example.com/me", vec![]).unwrap();
+//! assert!(signed.get_header("Authorization").is_some());
+//!```
+
+use oauth1::client::{HTTPMethod, Transmission};
+use oauth1::client::quirks::Quirks;
+use oauth1::client::time::get_time;
+use oauth1::client::token_credentials::{self, TokenCredentials};
+use oauth1::client::response::CredentialsResponse;
+use oauth1::client::session::Session;
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::{Response, Transport};
+use oauth1::client::async_transport::{AsyncTransport, Pending};
+use crypto::SignatureMethod;
+use error::Error;
+use rustc_serialize::{Encodable, Encoder, Decodable, Decoder};
+use std::default::Default;
+use std::fmt;
+use std::sync::RwLock;
+
+/// Printed by `Debug` in place of secrets
+const REDACTED : &'static str = "<redacted>";
+
+/// The consumer key and secret identifying a client
+#[derive(Clone, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct ConsumerCredentials {
+    key : String,
+    secret : String,
+}
+
+impl ConsumerCredentials {
+    pub fn new(key: &str, secret: &str) -> ConsumerCredentials {
+        ConsumerCredentials { key : key.to_string(), secret : secret.to_string() }
+    }
+
+    pub fn get_key(&self) -> &str {
+        self.key.as_slice()
+    }
+
+    pub fn get_secret(&self) -> &str {
+        self.secret.as_slice()
+    }
+}
+
+/// A token and its secret, either temporary or issued at the end of the three-legged flow.
+/// Providers implementing the OAuth Session Extension also issue a session handle and
+/// an expiry, used to renew the token
+#[derive(Clone, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct TokenPair {
+    token : String,
+    secret : String,
+    session_handle : Option<String>,
+    expires_at : Option<i64>,
+}
+
+impl fmt::Debug for ConsumerCredentials {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ConsumerCredentials {{ key: {:?}, secret: {} }}", self.key, REDACTED)
+    }
+}
+
+impl fmt::Debug for TokenPair {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let handle = if self.session_handle.is_some() { REDACTED } else { "None" };
+        write!(f, "TokenPair {{ token: {:?}, secret: {}, session_handle: {}, expires_at: {:?} }}",
+               self.token, REDACTED, handle, self.expires_at)
+    }
+}
+
+impl TokenPair {
+    pub fn new(token: &str, secret: &str) -> TokenPair {
+        TokenPair {
+            token : token.to_string(),
+            secret : secret.to_string(),
+            session_handle : None,
+            expires_at : None,
+        }
+    }
+
+    /// Keeps the `oauth_token` and `oauth_token_secret` of a provider's response, along
+    /// with `oauth_session_handle` and the expiry given by `oauth_expires_in`. The expiry
+    /// is kept by the provider's clock, `clock_offset` seconds ahead of the local one
+    pub fn from_response(response: &CredentialsResponse, clock_offset: i64) -> TokenPair {
+        let mut token = TokenPair::new(response.get_token(), response.get_token_secret());
+        token.session_handle = response.get_session_handle().map(|h| h.to_string());
+        token.expires_at = response.get_expires_in().map(|e| get_time().sec + clock_offset + e);
+        token
+    }
+
+    pub fn get_token(&self) -> &str {
+        self.token.as_slice()
+    }
+
+    pub fn get_secret(&self) -> &str {
+        self.secret.as_slice()
+    }
+
+    /// Returns `oauth_session_handle`, if the token can be renewed
+    pub fn get_session_handle(&self) -> Option<&str> {
+        self.session_handle.as_ref().map(|h| h.as_slice())
+    }
+
+    pub fn set_session_handle(&mut self, session_handle: Option<&str>) {
+        self.session_handle = session_handle.map(|h| h.to_string());
+    }
+
+    /// Returns when the token expires, in seconds since the epoch by the provider's clock
+    pub fn get_expires_at(&self) -> Option<i64> {
+        self.expires_at
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: Option<i64>) {
+        self.expires_at = expires_at;
+    }
+
+    /// Whether the token expires within `margin` seconds, by the provider's clock
+    /// `clock_offset` seconds ahead of the local one. Tokens without an expiry never do
+    pub fn expires_within(&self, margin: i64, clock_offset: i64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at - (get_time().sec + clock_offset) <= margin,
+            None => false
+        }
+    }
+}
+
+/// A value replaced through `&self`, so an `OwnedSession` shared between threads keeps
+/// what its requests learn. It compares, prints and serializes as the value it holds
+struct Shared<T>(RwLock<T>);
+
+impl<T: Clone> Shared<T> {
+    fn new(value: T) -> Shared<T> {
+        Shared(RwLock::new(value))
+    }
+
+    fn get(&self) -> T {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set(&self, value: T) {
+        *self.0.write().unwrap() = value;
+    }
+}
+
+impl<T: Clone> Clone for Shared<T> {
+    fn clone(&self) -> Shared<T> {
+        Shared::new(self.get())
+    }
+}
+
+impl<T: Clone + PartialEq> PartialEq for Shared<T> {
+    fn eq(&self, other: &Shared<T>) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<T: Clone + Eq> Eq for Shared<T> {}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for Shared<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), f)
+    }
+}
+
+impl<T: Clone + Encodable> Encodable for Shared<T> {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        self.get().encode(s)
+    }
+}
+
+impl<T: Clone + Decodable> Decodable for Shared<T> {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Shared<T>, D::Error> {
+        Ok(Shared::new(try!(Decodable::decode(d))))
+    }
+}
+
+/// An owned counterpart to `Session`. Each request is signed by a short-lived `Session`
+/// with its own nonce and timestamp, so signing only takes `&self`: an `OwnedSession`
+/// is `Send + Sync`, and one instance can be shared between worker threads with an
+/// `Arc`. The clock offset learned from a refused timestamp, and the token renewed
+/// before a request, are kept for every later request. Without a token, requests are
+/// two-legged, as with `Session::consumer_only`
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct OwnedSession {
+    consumer : ConsumerCredentials,
+    token : Shared<Option<TokenPair>>,
+    realm : Option<String>,
+    signature_method : SignatureMethod,
+    transmission : Transmission,
+    oauth_version : bool,
+    quirks : Quirks,
+    clock_offset : Shared<i64>,
+    retry : bool,
+    renewal_url : Option<String>,
+    renewal_margin : i64,
+}
+
+impl OwnedSession {
+    pub fn new(consumer: ConsumerCredentials, token: Option<TokenPair>,
+               signature_method: SignatureMethod) -> OwnedSession {
+        OwnedSession {
+            consumer : consumer,
+            token : Shared::new(token),
+            realm : None,
+            signature_method : signature_method,
+            transmission : Default::default(),
+            oauth_version : true,
+            quirks : Default::default(),
+            clock_offset : Shared::new(0),
+            retry : false,
+            renewal_url : None,
+            renewal_margin : 0,
+        }
+    }
+
+    pub fn set_realm(mut self, realm: &str) -> OwnedSession {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    /// Chooses where the OAuth parameters are placed in requests. Defaults to
+    /// `Transmission::Header`
+    pub fn set_transmission(mut self, transmission: Transmission) -> OwnedSession {
+        self.transmission = transmission;
+        self
+    }
+
+    /// Chooses whether `oauth_version=1.0` is sent, see `Session::set_oauth_version`
+    pub fn set_oauth_version(mut self, oauth_version: bool) -> OwnedSession {
+        self.oauth_version = oauth_version;
+        self
+    }
+
+    /// Follows `quirks` when signing, see `Session::set_quirks`
+    pub fn set_quirks(mut self, quirks: Quirks) -> OwnedSession {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Adds `offset` seconds to request timestamps, see `Session::set_clock_offset`
+    pub fn set_clock_offset(self, offset: i64) -> OwnedSession {
+        self.clock_offset.set(offset);
+        self
+    }
+
+    /// Retries refused requests once, see `Session::set_retry`
+    pub fn set_retry(mut self, retry: bool) -> OwnedSession {
+        self.retry = retry;
+        self
+    }
+
+    /// Makes `request` and `request_async` renew the token at `request_url` first, when
+    /// it expires within `margin` seconds. `AccountManager` renews the tokens of its
+    /// accounts the same way
+    pub fn set_auto_renew(mut self, request_url: &str, margin: i64) -> OwnedSession {
+        self.renewal_url = Some(request_url.to_string());
+        self.renewal_margin = margin;
+        self
+    }
+
+    /// Learns the provider's clock offset from `response`, see `Session::learn_clock_offset`
+    pub fn learn_clock_offset(&self, response: &Response) -> bool {
+        let mut session = self.session_for(None);
+        if !session.learn_clock_offset(response) {
+            return false;
+        }
+        self.clock_offset.set(session.get_clock_offset());
+        true
+    }
+
+    /// Replaces the token, for instance once token credentials have been issued
+    pub fn set_token(&mut self, token: Option<TokenPair>) {
+        self.token.set(token);
+    }
+
+    pub fn get_consumer(&self) -> &ConsumerCredentials {
+        &self.consumer
+    }
+
+    /// Returns a copy of the token, which requests may renew meanwhile
+    pub fn get_token(&self) -> Option<TokenPair> {
+        self.token.get()
+    }
+
+    pub fn get_signature_method(&self) -> SignatureMethod {
+        self.signature_method
+    }
+
+    /// Returns the clock offset, including any learned from the provider
+    pub fn get_clock_offset(&self) -> i64 {
+        self.clock_offset.get()
+    }
+
+    /// Borrows the consumer credentials and configuration as a `Session` signing with
+    /// `token`, in place of the session's own token
+    pub fn session_with_token<'a>(&'a self, token: &'a TokenPair) -> Session<'a> {
+        self.session_for(Some(token))
+    }
+
+    /// Runs `f` with a `Session` signing with `token`, or the session's own token if
+    /// `None`, and keeps the clock offset it learned
+    fn with_session<R, F>(&self, token: Option<&TokenPair>, f: F) -> R where F: FnOnce(&mut Session) -> R {
+        let own = match token {
+            Some(_) => None,
+            None => self.token.get()
+        };
+        let mut session = self.session_for(token.or(own.as_ref()));
+        let offset = session.get_clock_offset();
+        let result = f(&mut session);
+        self.keep_clock_offset(offset, session.get_clock_offset());
+        result
+    }
+
+    /// Stores `learned` if a request changed the clock offset from `start`, so an offset
+    /// learned meanwhile by a request on another thread isn't undone
+    fn keep_clock_offset(&self, start: i64, learned: i64) {
+        if learned != start {
+            self.clock_offset.set(learned);
+        }
+    }
+
+    fn session_for<'a>(&'a self, token: Option<&'a TokenPair>) -> Session<'a> {
+        let session = match token {
+            Some(t) => Session::new(self.consumer.get_key(), self.consumer.get_secret(),
+                                        t.get_token(), t.get_secret(), self.signature_method),
+            None => Session::consumer_only(self.consumer.get_key(), self.consumer.get_secret(),
+                                           self.signature_method),
+        };
+        let session = session.set_transmission(self.transmission)
+                             .set_oauth_version(self.oauth_version)
+                             .set_quirks(self.quirks.clone())
+                             .set_clock_offset(self.clock_offset.get())
+                             .set_retry(self.retry);
+        match self.realm {
+            Some(ref realm) => session.set_realm(realm.as_slice()),
+            None => session
+        }
+    }
+
+    /// Renews the token at the token endpoint `request_url`, with the OAuth Session
+    /// Extension. The request is signed with the current token, which is then replaced
+    pub fn renew<T: Transport>(&self, transport: &T, request_url: &str) -> Result<(), Error> {
+        self.renew_own(request_url, move |r| r.request(transport))
+    }
+
+    /// Renews `token` at the token endpoint `request_url`, with the OAuth Session
+    /// Extension, and returns the renewed token. The session's own token is left as is
+    pub fn renew_token<T: Transport>(&self, token: &TokenPair, transport: &T,
+                                     request_url: &str) -> Result<TokenPair, Error> {
+        self.renew_with(token, request_url, move |r| r.request(transport))
+    }
+
+    /// Renews `token` if `set_auto_renew` was used and it expires within the margin,
+    /// returning the renewed token
+    pub fn renew_if_due<T: Transport>(&self, token: &TokenPair,
+                                      transport: &T) -> Result<Option<TokenPair>, Error> {
+        match self.renewal_due(Some(token)) {
+            Some(url) => self.renew_with(token, url.as_slice(), move |r| r.request(transport)).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Like `renew_if_due`, but renews through an `AsyncTransport`, blocking until the
+    /// provider answers. Only tokens about to expire are renewed, so this seldom blocks
+    pub fn renew_if_due_async<T: AsyncTransport>(&self, token: &TokenPair,
+                                                 transport: &T) -> Result<Option<TokenPair>, Error> {
+        match self.renewal_due(Some(token)) {
+            Some(url) => self.renew_with(token, url.as_slice(), move |r| {
+                r.request_async(transport).and_then(|pending| pending.wait())
+            }).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Returns the url to renew `token` at, if `set_auto_renew` was used and the token
+    /// expires within the margin
+    fn renewal_due(&self, token: Option<&TokenPair>) -> Option<String> {
+        match (&self.renewal_url, token) {
+            (&Some(ref url), Some(token)) if token.expires_within(self.renewal_margin, self.clock_offset.get()) => {
+                Some(url.clone())
+            },
+            _ => None
+        }
+    }
+
+    /// Renews the session's own token, sending the request with `send`. Requests on
+    /// other threads keep signing with the old token until the renewed one is stored
+    fn renew_own<F>(&self, request_url: &str, send: F) -> Result<(), Error>
+                    where F: FnOnce(&mut TokenCredentials) -> Result<CredentialsResponse, Error> {
+        let token = match self.token.get() {
+            Some(t) => t,
+            None => return Err(Error::NotRenewable),
+        };
+        let renewed = try!(self.renew_with(&token, request_url, send));
+        self.token.set(Some(renewed));
+        Ok(())
+    }
+
+    /// Renews the session's own token with `send` first, if it is about to expire
+    fn renew_own_if_due<F>(&self, send: F) -> Result<(), Error>
+                           where F: FnOnce(&mut TokenCredentials) -> Result<CredentialsResponse, Error> {
+        let token = self.token.get();
+        let renewal = self.renewal_due(token.as_ref());
+        match renewal {
+            Some(url) => self.renew_own(url.as_slice(), send),
+            None => Ok(())
+        }
+    }
+
+    fn renew_with<F>(&self, token: &TokenPair, request_url: &str, send: F) -> Result<TokenPair, Error>
+                     where F: FnOnce(&mut TokenCredentials) -> Result<CredentialsResponse, Error> {
+        let handle = match token.get_session_handle() {
+            Some(h) => h,
+            None => return Err(Error::NotRenewable),
+        };
+        let offset = self.clock_offset.get();
+        let builder = token_credentials::Builder::renew(request_url, self.consumer.get_key(),
+                                                        self.consumer.get_secret(), token.get_token(),
+                                                        token.get_secret(), handle, self.signature_method)
+                          .set_quirks(self.quirks.clone())
+                          .set_clock_offset(offset)
+                          .set_retry(self.retry);
+        let builder = if self.oauth_version { builder.use_version() } else { builder };
+        let builder = match self.realm {
+            Some(ref realm) => builder.set_realm(realm.as_slice()),
+            None => builder
+        };
+        let mut request = builder.create();
+        let response = send(&mut request);
+        // an offset learned from a refused timestamp is kept, even if the renewal failed
+        self.keep_clock_offset(offset, request.get_clock_offset());
+        let mut renewed = TokenPair::from_response(&try!(response), request.get_clock_offset());
+        if renewed.session_handle.is_none() {
+            renewed.session_handle = token.session_handle.clone();
+        }
+        Ok(renewed)
+    }
+
+    /// Warns that a token due for renewal is used to sign a request which is not sent
+    /// here, so it cannot be renewed first
+    fn warn_if_due(&self, token: Option<&TokenPair>) {
+        if self.renewal_due(token).is_some() {
+            warn!("signing with a token which expires within {} seconds; renew it first, or \
+                   send the request with request()", self.renewal_margin);
+        }
+    }
+
+    /// Signs a request without sending it, see `Session::sign`. The token is never
+    /// renewed here, as there is no transport to renew it with
+    pub fn sign(&self, method: HTTPMethod, base_url: &str,
+                data: Vec<(&str, &str)>) -> Result<SignedRequest, Error> {
+        let token = self.token.get();
+        self.warn_if_due(token.as_ref());
+        self.with_session(token.as_ref(), move |s| s.sign(method, base_url, data))
+    }
+
+    /// Signs a request and sends it with `transport`, see `Session::request`. The token
+    /// is renewed first when it is about to expire, see `set_auto_renew`. A clock
+    /// offset learned from the response is kept for later requests
+    pub fn request<T: Transport>(&self, transport: &T, method: HTTPMethod, base_url: &str,
+                                 data: Vec<(&str, &str)>) -> Result<Response, Error> {
+        try!(self.renew_own_if_due(move |r| r.request(transport)));
+        self.with_session(None, move |s| s.request(transport, method, base_url, data))
+    }
+
+    /// Signs a request and hands it to `transport`, see `Session::request_async`. A
+    /// token about to expire is renewed first, waiting for the provider's answer
+    pub fn request_async<T: AsyncTransport>(&self, transport: &T, method: HTTPMethod,
+                                            base_url: &str, data: Vec<(&str, &str)>)
+                                            -> Result<Pending<Response>, Error> {
+        try!(self.renew_own_if_due(move |r| r.request_async(transport).and_then(|pending| pending.wait())));
+        self.with_session(None, move |s| s.request_async(transport, method, base_url, data))
+    }
+
+    /// Signs a request on behalf of `token`, without sending it. The session's own
+    /// token, if any, is ignored
+    pub fn sign_with_token(&self, token: &TokenPair, method: HTTPMethod, base_url: &str,
+                           data: Vec<(&str, &str)>) -> Result<SignedRequest, Error> {
+        self.warn_if_due(Some(token));
+        self.with_session(Some(token), move |s| s.sign(method, base_url, data))
+    }
+
+    /// Signs a request on behalf of `token` and sends it with `transport`. A clock
+    /// offset learned from the response is kept for later requests. `token` is not
+    /// renewed, see `renew_if_due`
+    pub fn request_with_token<T: Transport>(&self, token: &TokenPair, transport: &T,
+                                            method: HTTPMethod, base_url: &str,
+                                            data: Vec<(&str, &str)>) -> Result<Response, Error> {
+        self.with_session(Some(token), move |s| s.request(transport, method, base_url, data))
+    }
+
+    /// Signs a request on behalf of `token` and hands it to `transport`
+    pub fn request_async_with_token<T: AsyncTransport>(&self, token: &TokenPair, transport: &T,
+                                                       method: HTTPMethod, base_url: &str,
+                                                       data: Vec<(&str, &str)>)
+                                                       -> Result<Pending<Response>, Error> {
+        self.with_session(Some(token), move |s| s.request_async(transport, method, base_url, data))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{ConsumerCredentials, TokenPair, OwnedSession};
+    use oauth1::client::{HTTPMethod, Transmission};
+    use oauth1::client::response::CredentialsResponse;
+    use oauth1::client::session::Session;
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::time::get_time;
+    use oauth1::client::transport::Response;
+    use oauth1::client::async_transport::Reply;
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    fn owned() -> OwnedSession {
+        OwnedSession::new(ConsumerCredentials::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44"),
+                          Some(TokenPair::new("nnch734d00sl2jdk", "pfkkdhi9sl3r4s00")),
+                          SignatureMethod::HMACSHA1)
+    }
+
+    #[test]
+    fn owned_session_sign() {
+        let session = owned().set_realm("Photos");
+        let signed = session.sign(HTTPMethod::GET, "http://photos.example.net/photos",
+                                  vec![("file", "vacation.jpg")]).unwrap();
+        let header = signed.get_header("Authorization").unwrap();
+        assert!(header.starts_with("OAuth realm=\"Photos\", oauth_consumer_key=\"dpf43f3p2l4k3l03\""));
+        assert!(header.contains("oauth_token=\"nnch734d00sl2jdk\""));
+
+        let expected = Session::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44", "nnch734d00sl2jdk",
+                                    "pfkkdhi9sl3r4s00", SignatureMethod::HMACSHA1)
+                           .generate_signature(signed.get_base_string().to_string()).unwrap();
+        assert_eq!(signed.get_signature(), expected.as_slice());
+    }
+
+    #[test]
+    fn owned_session_without_token() {
+        let session = OwnedSession::new(ConsumerCredentials::new("key", "secret"), None,
+                                        SignatureMethod::PLAINTEXT)
+                          .set_transmission(Transmission::Query);
+        let signed = session.sign(HTTPMethod::GET, "http://example.com/", vec![]).unwrap();
+        assert_eq!(signed.get_signature(), "secret&");
+        assert!(!signed.get_url().contains("oauth_token"));
+    }
+
+    #[test]
+    fn owned_session_from_session() {
+        let session = Session::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44", "nnch734d00sl2jdk",
+                                   "pfkkdhi9sl3r4s00", SignatureMethod::HMACSHA1)
+                          .set_realm("Photos");
+        assert_eq!(session.to_owned_session(), owned().set_realm("Photos"));
+    }
+
+    #[test]
+    fn token_credentials_from_response() {
+        let resp = CredentialsResponse::parse(Some("application/x-www-form-urlencoded"),
+                                              b"oauth_token=nnch734d00sl2jdk&oauth_token_secret=pfkkdhi9sl3r4s00")
+                       .unwrap();
+        let mut session = OwnedSession::new(ConsumerCredentials::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44"),
+                                            None, SignatureMethod::HMACSHA1);
+        session.set_token(Some(TokenPair::from_response(&resp, 0)));
+        assert_eq!(session, owned());
+    }
+
+    #[test]
+    fn owned_session_sign_with_token() {
+        let consumer = OwnedSession::new(ConsumerCredentials::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44"),
+                                         None, SignatureMethod::HMACSHA1);
+        let token = TokenPair::new("nnch734d00sl2jdk", "pfkkdhi9sl3r4s00");
+        let signed = consumer.sign_with_token(&token, HTTPMethod::GET, "http://photos.example.net/photos",
+                                              vec![]).unwrap();
+        assert!(signed.get_header("Authorization").unwrap().contains("oauth_token=\"nnch734d00sl2jdk\""));
+        let expected = consumer.session_with_token(&token)
+                               .generate_signature(signed.get_base_string().to_string()).unwrap();
+        assert_eq!(signed.get_signature(), expected.as_slice());
+
+        // the session's own token is only replaced for that request
+        let session = owned();
+        let other = TokenPair::new("other", "other_secret");
+        let signed = session.sign_with_token(&other, HTTPMethod::GET, "http://photos.example.net/photos",
+                                             vec![]).unwrap();
+        assert!(signed.get_header("Authorization").unwrap().contains("oauth_token=\"other\""));
+        assert_eq!(session.get_token().unwrap().get_token(), "nnch734d00sl2jdk");
+    }
+
+    fn renewal_transport(req: &SignedRequest) -> Result<Response, Error> {
+        if req.get_url() == "https://example.com/token" {
+            assert!(req.get_header("Authorization").unwrap().contains("oauth_session_handle=\"handle\""));
+            Ok(Response::new(200, vec![], b"oauth_token=renewed&oauth_token_secret=renewed_secret\
+                                           &oauth_expires_in=3600".to_vec()))
+        } else {
+            assert!(req.get_header("Authorization").unwrap().contains("oauth_token=\"renewed\""));
+            Ok(Response::new(200, vec![], vec![]))
+        }
+    }
+
+    fn expiring_session(expires_in: i64) -> OwnedSession {
+        let mut token = TokenPair::new("old", "old_secret");
+        token.set_session_handle(Some("handle"));
+        token.set_expires_at(Some(get_time().sec + expires_in));
+        OwnedSession::new(ConsumerCredentials::new("key", "secret"), Some(token), SignatureMethod::HMACSHA1)
+    }
+
+    #[test]
+    fn token_credentials_session_extension() {
+        let resp = CredentialsResponse::parse(None, b"oauth_token=t&oauth_token_secret=s&oauth_expires_in=3600\
+                                                      &oauth_session_handle=handle").unwrap();
+        let token = TokenPair::from_response(&resp, 0);
+        assert_eq!(token.get_session_handle(), Some("handle"));
+        assert!(token.expires_within(3600, 0));
+        assert!(!token.expires_within(3000, 0));
+        assert!(!TokenPair::new("t", "s").expires_within(3600, 0));
+
+        // the expiry is kept by the provider's clock, ten minutes ahead here
+        let token = TokenPair::from_response(&resp, 600);
+        assert!((token.get_expires_at().unwrap() - (get_time().sec + 4200)).abs() <= 1);
+        assert!(!token.expires_within(3000, 600));
+        let mut token = TokenPair::new("t", "s");
+        token.set_expires_at(Some(get_time().sec + 600));
+        assert!(!token.expires_within(300, 0));
+        assert!(token.expires_within(300, 600));
+    }
+
+    #[test]
+    fn owned_session_renew() {
+        let session = expiring_session(60);
+        session.renew(&renewal_transport, "https://example.com/token").unwrap();
+        let token = session.get_token().unwrap();
+        assert_eq!(token.get_token(), "renewed");
+        assert_eq!(token.get_secret(), "renewed_secret");
+        // the handle is kept when the provider doesn't send a new one
+        assert_eq!(token.get_session_handle(), Some("handle"));
+        assert!(!token.expires_within(3000, 0));
+
+        let session = owned();
+        match session.renew(&renewal_transport, "https://example.com/token") {
+            Err(Error::NotRenewable) => (),
+            _ => panic!("expected Error::NotRenewable")
+        }
+    }
+
+    #[test]
+    fn owned_session_renew_follows_configuration() {
+        use oauth1::client::quirks::{Quirks, TimestampUnit};
+        let quirks = Quirks::new().set_quoted(false).set_timestamp_unit(TimestampUnit::Milliseconds);
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            let header = req.get_header("Authorization").unwrap();
+            assert!(header.starts_with("OAuth realm=Photos, "));
+            assert!(header.contains("oauth_session_handle=handle"));
+            assert!(!header.contains("oauth_version"));
+            let timestamp : i64 = req.get_timestamp().unwrap().parse().unwrap();
+            assert!(timestamp > get_time().sec * 100);
+            Ok(Response::new(200, vec![], b"oauth_token=renewed&oauth_token_secret=renewed_secret".to_vec()))
+        };
+        let session = expiring_session(60).set_quirks(quirks)
+                                          .set_oauth_version(false)
+                                          .set_realm("Photos");
+        session.renew(&transport, "https://example.com/token").unwrap();
+        assert_eq!(session.get_token().unwrap().get_token(), "renewed");
+
+        // oauth_version is sent by default, as for other requests
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert!(req.get_header("Authorization").unwrap().contains("oauth_version=\"1.0\""));
+            Ok(Response::new(200, vec![], b"oauth_token=renewed&oauth_token_secret=renewed_secret".to_vec()))
+        };
+        expiring_session(60).renew(&transport, "https://example.com/token").unwrap();
+    }
+
+    #[test]
+    fn owned_session_auto_renew() {
+        let session = expiring_session(60).set_auto_renew("https://example.com/token", 300);
+        session.request(&renewal_transport, HTTPMethod::GET, "https://example.com/me", vec![]).unwrap();
+        assert_eq!(session.get_token().unwrap().get_token(), "renewed");
+
+        // also before handing a request to an asynchronous transport
+        let session = expiring_session(60).set_auto_renew("https://example.com/token", 300);
+        let transport = |req: SignedRequest, reply: Reply| reply.complete(renewal_transport(&req));
+        session.request_async(&transport, HTTPMethod::GET, "https://example.com/me", vec![]).unwrap()
+               .wait().unwrap();
+        assert_eq!(session.get_token().unwrap().get_token(), "renewed");
+
+        // the expiry is judged by the provider's clock
+        let session = expiring_session(600).set_auto_renew("https://example.com/token", 300)
+                                           .set_clock_offset(400);
+        session.request(&renewal_transport, HTTPMethod::GET, "https://example.com/me", vec![]).unwrap();
+        assert_eq!(session.get_token().unwrap().get_token(), "renewed");
+
+        // far from expiring, so the old token is used as is
+        let session = expiring_session(3600).set_auto_renew("https://example.com/token", 300);
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert!(req.get_header("Authorization").unwrap().contains("oauth_token=\"old\""));
+            Ok(Response::new(200, vec![], vec![]))
+        };
+        session.request(&transport, HTTPMethod::GET, "https://example.com/me", vec![]).unwrap();
+    }
+
+    #[test]
+    fn owned_session_is_send() {
+        fn assert_send<T: Send + Sync>(_: &T) {}
+        assert_send(&owned());
+    }
+
+    #[test]
+    fn owned_session_keeps_clock_offset() {
+        let server_time = get_time().sec + 600;
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            let timestamp : i64 = req.get_timestamp().unwrap().parse().unwrap();
+            if (timestamp - server_time).abs() > 5 {
+                let header = format!("OAuth oauth_problem=\"timestamp_refused\", \
+                                      oauth_acceptable_timestamps=\"{}-{}\"", server_time - 300, server_time + 300);
+                Ok(Response::new(401, vec![("WWW-Authenticate".to_string(), header)], vec![]))
+            } else {
+                Ok(Response::new(200, vec![], vec![]))
+            }
+        };
+        let session = owned();
+        assert_eq!(session.request(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_status(), 401);
+        assert!((session.get_clock_offset() - 600).abs() <= 5);
+        // the learned offset is used by the next request
+        assert_eq!(session.request(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_status(), 200);
+
+        let session = owned().set_retry(true);
+        let token = TokenPair::new("other", "other_secret");
+        assert_eq!(session.request_with_token(&token, &transport, HTTPMethod::GET, "http://example.com/", vec![])
+                          .unwrap().get_status(), 200);
+        assert!((session.get_clock_offset() - 600).abs() <= 5);
+    }
+
+    #[test]
+    fn owned_session_shared_between_threads() {
+        use std::sync::Arc;
+        use std::thread;
+        let session = Arc::new(owned());
+        let handles : Vec<_> = (0..4).map(|i| {
+            let session = session.clone();
+            thread::spawn(move || {
+                let url = format!("http://photos.example.net/photos/{}", i);
+                session.sign(HTTPMethod::GET, url.as_slice(), vec![]).unwrap()
+            })
+        }).collect();
+        let signed : Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let token = session.get_token().unwrap();
+        let mut checker = session.session_with_token(&token);
+        for (i, s) in signed.iter().enumerate() {
+            assert!(signed.iter().skip(i + 1).all(|o| o.get_nonce() != s.get_nonce()));
+            let expected = checker.generate_signature(s.get_base_string().to_string()).unwrap();
+            assert_eq!(s.get_signature(), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn owned_session_json() {
+        use rustc_serialize::json;
+        let session = owned().set_realm("Photos");
+        let saved = json::encode(&session).unwrap();
+        let restored : OwnedSession = json::decode(saved.as_slice()).unwrap();
+        assert_eq!(restored, session);
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let mut token = TokenPair::new("nnch734d00sl2jdk", "pfkkdhi9sl3r4s00");
+        token.set_session_handle(Some("AJ9m8fo.ZXyd"));
+        let session = OwnedSession::new(ConsumerCredentials::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44"),
+                                        Some(token), SignatureMethod::HMACSHA1);
+        let debug = format!("{:?}", session);
+        assert!(debug.contains("dpf43f3p2l4k3l03"));
+        assert!(debug.contains("nnch734d00sl2jdk"));
+        assert!(!debug.contains("kd94hf93k423kf44"));
+        assert!(!debug.contains("pfkkdhi9sl3r4s00"));
+        assert!(!debug.contains("AJ9m8fo.ZXyd"));
+    }
+}
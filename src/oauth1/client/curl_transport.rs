@@ -0,0 +1,78 @@
+//! `Transport` built on [curl-rust](https://github.com/carllerche/curl-rust), enabled
+//! with the `curl` cargo feature.
+//!
+//!# Example
+//!
+//!```no_run
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::HTTPMethod;
+//! use rust_oauth::oauth1::client::session::Session;
+//! use rust_oauth::oauth1::client::curl_transport::CurlTransport;
+//!
+//! let mut s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1);
+//! let resp = s.request(&CurlTransport::new(), HTTPMethod::POST,
+//!                      "https://api.twitter.com/1.1/statuses/update.json",
+//!                      vec![("status", "Hello%20Ladies")]).unwrap();
+//!```
+
+extern crate curl;
+
+use self::curl::http;
+use oauth1::client::HTTPMethod;
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::{Response, Transport};
+use error::Error;
+
+/// Sends requests with a new curl handle each time
+#[derive(Copy, Clone, Debug)]
+pub struct CurlTransport {
+    timeout : Option<usize>,
+}
+
+impl CurlTransport {
+    pub fn new() -> CurlTransport {
+        CurlTransport { timeout : None }
+    }
+
+    /// Aborts requests taking longer than `ms` milliseconds
+    pub fn set_timeout(mut self, ms: usize) -> CurlTransport {
+        self.timeout = Some(ms);
+        self
+    }
+}
+
+impl Transport for CurlTransport {
+    fn send(&self, request: &SignedRequest) -> Result<Response, Error> {
+        let mut handle = match self.timeout {
+            None => http::handle(),
+            Some(ms) => http::handle().timeout(ms),
+        };
+        let url = request.get_url();
+        let body = request.get_body().unwrap_or("");
+        let mut req = match *request.get_method() {
+            HTTPMethod::GET     => handle.get(url),
+            HTTPMethod::HEAD    => handle.head(url),
+            HTTPMethod::DELETE  => handle.delete(url),
+            HTTPMethod::OPTIONS => handle.options(url),
+            HTTPMethod::TRACE   => handle.trace(url),
+            HTTPMethod::POST    => handle.post(url, body),
+            HTTPMethod::PUT     => handle.put(url, body),
+            HTTPMethod::PATCH   => handle.patch(url, body),
+            // extension methods carry their data in the query, so they are sent as a GET
+            // whose method name curl replaces, with CURLOPT_CUSTOMREQUEST
+            HTTPMethod::Extension(ref m) => handle.get(url).custom_request(m.as_slice()),
+        };
+        for &(ref name, ref value) in request.get_headers().iter() {
+            req = req.header(name.as_slice(), value.as_slice());
+        }
+        let resp = try!(req.exec().map_err(|e| Error::Transport(Box::new(e))));
+
+        let mut headers = Vec::new();
+        for (name, values) in resp.get_headers().iter() {
+            for value in values.iter() {
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+        Ok(Response::new(resp.get_code(), headers, resp.get_body().to_vec()))
+    }
+}
@@ -0,0 +1,178 @@
+//! OAuth Echo, which lets a client prove the identity of its user to a third party,
+//! the delegate, without sharing its credentials. The client signs a `GET` of the
+//! service provider's `verify_credentials` url, and sends the resulting `Authorization`
+//! header to the delegate as `X-Verify-Credentials-Authorization`, along with the url
+//! as `X-Auth-Service-Provider`. The delegate sends that request to the service
+//! provider, which answers with the user when the signature is valid.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::Error;
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::echo::{self, Echo};
+//! use rust_oauth::oauth1::client::session::Session;
+//! use rust_oauth::oauth1::client::signed_request::SignedRequest;
+//! use rust_oauth::oauth1::client::transport::Response;
+//!
+//! let verify_url = "https://api.twitter.com/1.1/account/verify_credentials.json";
+//!
+//! // on the client, along with its request to the delegate
+//! let mut s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1);
+//! let headers = echo::headers(&mut s, verify_url).unwrap();
+//!
+//! // on the delegate
+//! let echo = Echo::from_headers(headers.as_slice()).unwrap();
+//! let transport = |_: &SignedRequest| -> Result<Response, Error> {
+//!     Ok(Response::new(200, vec![], b"{\"screen_name\":\"twitterapi\"}".to_vec()))
+//! };
+//! let user = echo.verify(&transport, &[verify_url]).unwrap();
+//! assert!(user.is_success());
+//!```
+
+use std::ascii::AsciiExt;
+use oauth1::client::{HTTPMethod, AuthorizationHeader};
+use oauth1::client::session::Session;
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::{Response, Transport};
+use error::Error;
+
+/// Header naming the url to verify the credentials with
+pub const SERVICE_PROVIDER : &'static str = "X-Auth-Service-Provider";
+/// Header holding the `Authorization` header signed for the service provider
+pub const VERIFY_CREDENTIALS_AUTHORIZATION : &'static str = "X-Verify-Credentials-Authorization";
+
+/// Signs a `GET` of `verify_credentials_url` with `session`, and returns the headers to
+/// send to the delegate. The `Authorization` header is produced whatever the session's
+/// `Transmission`, following its quirks
+pub fn headers(session: &mut Session, verify_credentials_url: &str) -> Result<Vec<(String, String)>, Error> {
+    try!(session.sign(HTTPMethod::GET, verify_credentials_url, vec![]));
+    Ok(vec![(String::from_str(SERVICE_PROVIDER), verify_credentials_url.to_string()),
+            (String::from_str(VERIFY_CREDENTIALS_AUTHORIZATION), session.get_header())])
+}
+
+/// OAuth Echo headers received by the delegate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Echo {
+    service_provider : String,
+    authorization : String,
+}
+
+impl Echo {
+    pub fn new(service_provider: &str, authorization: &str) -> Echo {
+        Echo {
+            service_provider : service_provider.to_string(),
+            authorization : authorization.to_string(),
+        }
+    }
+
+    /// Finds the Echo headers among the headers of an incoming request, compared
+    /// case-insensitively. Returns `None` if either is missing
+    pub fn from_headers(headers: &[(String, String)]) -> Option<Echo> {
+        let find = |name: &str| -> Option<&str> {
+            headers.iter()
+                   .find(|&&(ref k, _)| k.as_slice().eq_ignore_ascii_case(name))
+                   .map(|&(_, ref v)| v.as_slice())
+        };
+        match (find(SERVICE_PROVIDER), find(VERIFY_CREDENTIALS_AUTHORIZATION)) {
+            (Some(provider), Some(authorization)) => Some(Echo::new(provider, authorization)),
+            _ => None
+        }
+    }
+
+    pub fn get_service_provider(&self) -> &str {
+        self.service_provider.as_slice()
+    }
+
+    pub fn get_authorization(&self) -> &str {
+        self.authorization.as_slice()
+    }
+
+    /// Returns the request to send to the service provider
+    pub fn to_request(&self) -> SignedRequest {
+        SignedRequest::presigned(HTTPMethod::GET, self.service_provider.as_slice(),
+                                 self.authorization.as_slice())
+    }
+
+    /// Sends the signed request to the service provider with `transport`, and returns its
+    /// response, whose body describes the user. `trusted` lists the urls the delegate
+    /// accepts: the service provider is chosen by the client, so any other url fails
+    /// with `Error::UntrustedProvider` without being requested. A problem reported by
+    /// the service provider fails with `Error::Problem`; other failures are returned
+    /// as is, to be checked with `Response::is_success`
+    pub fn verify<T: Transport>(&self, transport: &T, trusted: &[&str]) -> Result<Response, Error> {
+        if !trusted.iter().any(|url| *url == self.service_provider.as_slice()) {
+            return Err(Error::UntrustedProvider(self.service_provider.clone()));
+        }
+        let response = try!(transport.send(&self.to_request()));
+        try!(response.check_problem());
+        Ok(response)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Echo, headers};
+    use oauth1::client::{HTTPMethod, Transmission};
+    use oauth1::client::session::Session;
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::transport::Response;
+    use oauth1::client::problem::ProblemKind;
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    const VERIFY_URL : &'static str = "https://api.twitter.com/1.1/account/verify_credentials.json";
+
+    #[test]
+    fn echo_headers_test() {
+        let mut s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1)
+                        .set_transmission(Transmission::Query);
+        let headers = headers(&mut s, VERIFY_URL).unwrap();
+        assert_eq!(headers[0], ("X-Auth-Service-Provider".to_string(), VERIFY_URL.to_string()));
+        assert_eq!(headers[1].0.as_slice(), "X-Verify-Credentials-Authorization");
+        assert!(headers[1].1.starts_with("OAuth oauth_consumer_key=\"key\", "));
+        assert!(headers[1].1.contains("oauth_token=\"token\""));
+    }
+
+    #[test]
+    fn echo_verify_test() {
+        let received = vec![("x-auth-service-provider".to_string(), VERIFY_URL.to_string()),
+                            ("x-verify-credentials-authorization".to_string(), "OAuth oauth_token=\"t\"".to_string())];
+        let echo = Echo::from_headers(received.as_slice()).unwrap();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(*req.get_method(), HTTPMethod::GET);
+            assert_eq!(req.get_url(), VERIFY_URL);
+            assert_eq!(req.get_header("Authorization"), Some("OAuth oauth_token=\"t\""));
+            Ok(Response::new(200, vec![], b"{\"screen_name\":\"twitterapi\"}".to_vec()))
+        };
+        let resp = echo.verify(&transport, &[VERIFY_URL]).unwrap();
+        assert_eq!(resp.get_body_str(), Some("{\"screen_name\":\"twitterapi\"}"));
+    }
+
+    #[test]
+    fn echo_untrusted_provider_test() {
+        let echo = Echo::new("https://evil.example.com/verify", "OAuth oauth_token=\"t\"");
+        let transport = |_: &SignedRequest| -> Result<Response, Error> {
+            panic!("untrusted provider requested")
+        };
+        match echo.verify(&transport, &[VERIFY_URL]) {
+            Err(Error::UntrustedProvider(ref url)) => assert_eq!(url.as_slice(), "https://evil.example.com/verify"),
+            _ => panic!("expected Error::UntrustedProvider")
+        }
+    }
+
+    #[test]
+    fn echo_problem_test() {
+        let echo = Echo::new(VERIFY_URL, "OAuth oauth_token=\"t\"");
+        let transport = |_: &SignedRequest| -> Result<Response, Error> {
+            Ok(Response::new(401, vec![("WWW-Authenticate".to_string(),
+                                        "OAuth oauth_problem=\"token_revoked\"".to_string())], vec![]))
+        };
+        match echo.verify(&transport, &[VERIFY_URL]) {
+            Err(Error::Problem(ref p)) => assert_eq!(*p.get_kind(), ProblemKind::TokenRevoked),
+            _ => panic!("expected Error::Problem")
+        }
+        assert!(Echo::from_headers(&[("X-Auth-Service-Provider".to_string(), VERIFY_URL.to_string())]).is_none());
+    }
+}
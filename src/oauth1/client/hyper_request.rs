@@ -0,0 +1,145 @@
+//! Signs [hyper](https://github.com/hyperium/hyper) client requests in place, enabled
+//! with the `hyper` cargo feature, for code building its own `hyper::client::Request`
+//! rather than going through `HyperTransport`.
+//!
+//! The method and url are read from the request. Its body is written only once the
+//! request is started, so the form-encoded parameters it will carry are given along
+//! with it. The OAuth parameters are then written to the `Authorization` header, or
+//! appended to the url query, according to the session's `Transmission`.
+//!
+//!# Example
+//!
+//!```no_run
+//! extern crate hyper;
+//! extern crate rust_oauth;
+//! use hyper::Url;
+//! use hyper::method::Method;
+//! use hyper::client::Request;
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::session::Session;
+//!
+//! fn main() {
+//!     let mut s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1);
+//!     let url = Url::parse("https://api.example.com/statuses").unwrap();
+//!     let mut req = Request::new(Method::Post, url).unwrap();
+//!     s.sign_hyper_request(&mut req, vec![("status", "hello%20world")]).unwrap();
+//! }
+//!```
+
+extern crate hyper;
+
+use self::hyper::Url;
+use self::hyper::method::Method;
+use self::hyper::client::Request;
+use self::hyper::net::Fresh;
+use oauth1::client::{HTTPMethod, Transmission, concat, percent_encode};
+use oauth1::client::session::Session;
+use error::Error;
+
+fn from_method(method: &Method) -> HTTPMethod {
+    match *method {
+        Method::Get     => HTTPMethod::GET,
+        Method::Head    => HTTPMethod::HEAD,
+        Method::Delete  => HTTPMethod::DELETE,
+        Method::Post    => HTTPMethod::POST,
+        Method::Put     => HTTPMethod::PUT,
+        Method::Patch   => HTTPMethod::PATCH,
+        Method::Options => HTTPMethod::OPTIONS,
+        Method::Trace   => HTTPMethod::TRACE,
+        ref other       => HTTPMethod::Extension(other.to_string()),
+    }
+}
+
+/// Where the signature of a request goes
+#[derive(Debug, PartialEq, Eq)]
+enum Signed {
+    /// Value of the `Authorization` header
+    Header(String),
+    /// Url to request instead, with the OAuth parameters in its query
+    Url(String),
+}
+
+/// Signs a request to `url`, independently of hyper
+fn sign_parts(session: &mut Session, method: HTTPMethod, url: &str,
+              data: Vec<(&str, &str)>) -> Result<Signed, Error> {
+    let transmission = session.get_transmission();
+    if transmission == Transmission::Body {
+        return Err(Error::UnsupportedTransmission(transmission));
+    }
+    let signed = try!(session.sign(method, url, data));
+    match transmission {
+        Transmission::Query => {
+            let query : Vec<String> = signed.get_oauth_parameters()
+                                            .iter()
+                                            .map(|&(ref k, ref v)| format!("{}={}", percent_encode(k.as_slice()),
+                                                                                    percent_encode(v.as_slice())))
+                                            .collect();
+            let sep = if url.contains_char('?') { "&" } else { "?" };
+            Ok(Signed::Url(format!("{}{}{}", url, sep, concat(query.as_slice(), "&"))))
+        },
+        _ => Ok(Signed::Header(signed.get_header("Authorization").unwrap_or("").to_string()))
+    }
+}
+
+impl<'a> Session<'a> {
+    /// Signs `request` in place. `data` holds the percent encoded parameters of the
+    /// form body it will be sent with, if any. Only `Transmission::Header` and
+    /// `Transmission::Query` can be applied without writing the body; `Transmission::Body`
+    /// fails with `Error::UnsupportedTransmission`
+    pub fn sign_hyper_request(&mut self, request: &mut Request<Fresh>,
+                              data: Vec<(&str, &str)>) -> Result<(), Error> {
+        let url = request.url.to_string();
+        match try!(sign_parts(self, from_method(&request.method()), url.as_slice(), data)) {
+            Signed::Header(header) => {
+                request.headers_mut().set_raw("Authorization", vec![header.into_bytes()]);
+            },
+            Signed::Url(signed_url) => {
+                request.url = try!(Url::parse(signed_url.as_slice())
+                                       .map_err(|_| Error::InvalidUrl(signed_url.clone())));
+            }
+        };
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Signed, sign_parts};
+    use oauth1::client::{HTTPMethod, Transmission};
+    use oauth1::client::session::Session;
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    fn session() -> Session<'static> {
+        Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7", "dh893hdasih9",
+                     SignatureMethod::HMACSHA1)
+    }
+
+    #[test]
+    fn sign_hyper_request_header() {
+        match sign_parts(&mut session(), HTTPMethod::POST, "http://example.com/request?b5=%3D%253D",
+                         vec![("c2", ""), ("a3", "2%20q")]).unwrap() {
+            Signed::Header(header) => assert!(header.starts_with("OAuth oauth_consumer_key=\"9djdj82h48djs9d2\"")),
+            other => panic!("expected a header, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn sign_hyper_request_query() {
+        let mut s = session().set_transmission(Transmission::Query);
+        match sign_parts(&mut s, HTTPMethod::DELETE, "http://example.com/request", vec![]).unwrap() {
+            Signed::Url(url) => assert!(url.starts_with("http://example.com/request?oauth_consumer_key=9djdj82h48djs9d2&")),
+            other => panic!("expected a url, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn sign_hyper_request_body_transmission() {
+        let mut s = session().set_transmission(Transmission::Body);
+        match sign_parts(&mut s, HTTPMethod::POST, "http://example.com/request", vec![]) {
+            Err(Error::UnsupportedTransmission(Transmission::Body)) => (),
+            _ => panic!("expected Error::UnsupportedTransmission")
+        }
+    }
+}
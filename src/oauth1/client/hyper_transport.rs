@@ -0,0 +1,80 @@
+//! `Transport` built on [hyper](https://github.com/hyperium/hyper), enabled with the
+//! `hyper` cargo feature.
+//!
+//!# Example
+//!
+//!```no_run
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::HTTPMethod;
+//! use rust_oauth::oauth1::client::session::Session;
+//! use rust_oauth::oauth1::client::hyper_transport::HyperTransport;
+//!
+//! let transport = HyperTransport::new();
+//! let mut s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1);
+//! let resp = s.request(&transport, HTTPMethod::GET,
+//!                      "https://api.twitter.com/1.1/statuses/user_timeline.json",
+//!                      vec![("screen_name", "twitterapi")]).unwrap();
+//!```
+
+extern crate hyper;
+
+use self::hyper::Client;
+use self::hyper::method::Method;
+use self::hyper::header::Headers;
+use std::io::Read;
+use oauth1::client::HTTPMethod;
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::{Response, Transport};
+use error::Error;
+
+/// Sends requests with one hyper `Client`, shared between calls and threads
+pub struct HyperTransport {
+    client : Client,
+}
+
+impl HyperTransport {
+    pub fn new() -> HyperTransport {
+        HyperTransport::with_client(Client::new())
+    }
+
+    /// Uses an already configured client
+    pub fn with_client(client: Client) -> HyperTransport {
+        HyperTransport { client : client }
+    }
+}
+
+fn to_method(method: &HTTPMethod) -> Method {
+    match *method {
+        HTTPMethod::GET     => Method::Get,
+        HTTPMethod::HEAD    => Method::Head,
+        HTTPMethod::DELETE  => Method::Delete,
+        HTTPMethod::POST    => Method::Post,
+        HTTPMethod::PUT     => Method::Put,
+        HTTPMethod::PATCH   => Method::Patch,
+        HTTPMethod::OPTIONS => Method::Options,
+        HTTPMethod::TRACE   => Method::Trace,
+        HTTPMethod::Extension(ref m) => Method::Extension(m.clone()),
+    }
+}
+
+impl Transport for HyperTransport {
+    fn send(&self, request: &SignedRequest) -> Result<Response, Error> {
+        let mut headers = Headers::new();
+        for &(ref name, ref value) in request.get_headers().iter() {
+            headers.set_raw(name.clone(), vec![value.as_bytes().to_vec()]);
+        }
+        let mut req = self.client.request(to_method(request.get_method()), request.get_url())
+                            .headers(headers);
+        if let Some(body) = request.get_body() {
+            req = req.body(body);
+        }
+        let mut resp = try!(req.send().map_err(|e| Error::Transport(Box::new(e))));
+
+        let headers = resp.headers.iter()
+                                  .map(|h| (h.name().to_string(), h.value_string()))
+                                  .collect();
+        let mut body = Vec::new();
+        try!(resp.read_to_end(&mut body).map_err(|e| Error::Transport(Box::new(e))));
+        Ok(Response::new(resp.status.to_u16() as u32, headers, body))
+    }
+}
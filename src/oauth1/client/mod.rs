@@ -2,22 +2,53 @@ extern crate time;
 extern crate url;
 extern crate rand;
 
-use self::url::{FORM_URLENCODED_ENCODE_SET, utf8_percent_encode};
-use self::time::now_utc;
+use self::url::{Url, FORM_URLENCODED_ENCODE_SET, utf8_percent_encode};
+use self::time::{now_utc, strptime};
 use self::rand::{OsRng, Rng};
 use std::fmt;
+use std::ascii::AsciiExt;
+use std::default::Default;
+use error::Error;
+use crypto::SignatureMethod;
+use self::quirks::{Quirks, NonceFormat, TimestampUnit};
+use self::problem::ProblemKind;
+use self::transport::Response;
 
+pub mod accounts;
+pub mod async_transport;
+pub mod credentials;
+#[cfg(feature = "curl")]
+pub mod curl_transport;
+pub mod echo;
+#[cfg(feature = "hyper")]
+pub mod hyper_request;
+#[cfg(feature = "hyper")]
+pub mod hyper_transport;
+pub mod problem;
+pub mod providers;
+pub mod quirks;
+pub mod response;
 pub mod session;
+pub mod signed_request;
 pub mod temporary_credentials;
+pub mod token_credentials;
+pub mod transport;
+pub mod xauth;
 
-#[derive(Copy, Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 #[unstable]
 pub enum HTTPMethod {
     GET,
     POST,
     DELETE,
     PUT,
-    HEAD
+    HEAD,
+    PATCH,
+    OPTIONS,
+    TRACE,
+    /// Any other method, such as WebDAV's `PROPFIND`. It is sent as given, and
+    /// signed in uppercase
+    Extension(String)
 }
 
 impl fmt::Display for HTTPMethod {
@@ -27,44 +58,228 @@ impl fmt::Display for HTTPMethod {
             HTTPMethod::POST    => "POST",
             HTTPMethod::DELETE  => "DELETE",
             HTTPMethod::PUT     => "PUT",
-            HTTPMethod::HEAD    => "HEAD"
+            HTTPMethod::HEAD    => "HEAD",
+            HTTPMethod::PATCH   => "PATCH",
+            HTTPMethod::OPTIONS => "OPTIONS",
+            HTTPMethod::TRACE   => "TRACE",
+            HTTPMethod::Extension(ref m) => m.as_slice()
         };
         write!(f, "{}", out)
     }
 }
 
+/// Where the OAuth protocol parameters are placed when a request is sent, as described
+/// in [RFC 5849 &sect; 3.5](http://tools.ietf.org/html/rfc5849#section-3.5)
+#[derive(Copy, Debug, PartialEq, Eq, Clone, RustcEncodable, RustcDecodable)]
+#[unstable]
+pub enum Transmission {
+    /// In the `Authorization` header
+    Header,
+    /// In a form-encoded entity-body. Only valid for single-part
+    /// `application/x-www-form-urlencoded` bodies, so `Session::sign` refuses it
+    /// for methods without a body, such as `GET` or `HEAD`
+    Body,
+    /// In the query component of the request URI
+    Query
+}
+
+impl Default for Transmission {
+    fn default() -> Transmission {
+        Transmission::Header
+    }
+}
+
+/// Whether `method` carries its data in a form-encoded body
+fn has_body(method: &HTTPMethod) -> bool {
+    match *method {
+        HTTPMethod::POST | HTTPMethod::PUT | HTTPMethod::PATCH => true,
+        _ => false
+    }
+}
 
 pub trait AuthorizationHeader {
-    fn get_header(&self) -> String;
+    /// Returns the OAuth protocol parameters of the last signed request, unencoded,
+    /// including `oauth_signature`
+    fn get_oauth_parameters(&self) -> Vec<(String, String)>;
+
+    /// Returns the `realm` sent along with the parameters, if any
+    fn get_realm(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns the provider quirks followed when serializing the header
+    fn get_quirks(&self) -> Quirks {
+        Default::default()
+    }
+
+    /// Returns the value of the `Authorization` header for the last signed request
+    fn get_header(&self) -> String {
+        self.get_quirks().header(self.get_realm(), self.get_oauth_parameters().as_slice())
+    }
+}
+
+/// Serializes OAuth protocol parameters into the value of an `Authorization` header as
+/// described in [RFC 5849 &sect; 3.5.1](http://tools.ietf.org/html/rfc5849#section-3.5.1).
+/// Every name and value is percent encoded; `realm` is only quoted, as it is not an
+/// OAuth parameter.
+///
+/// ```
+/// use rust_oauth::oauth1::client::authorization_header;
+/// let params = vec![("oauth_consumer_key".to_string(), "key".to_string()),
+///                   ("oauth_signature".to_string(), "a+b=".to_string())];
+/// assert_eq!(authorization_header(Some("Example"), params.as_slice()),
+///            "OAuth realm=\"Example\", oauth_consumer_key=\"key\", oauth_signature=\"a%2Bb%3D\"".to_string());
+/// ```
+pub fn authorization_header(realm: Option<&str>, params: &[(String, String)]) -> String {
+    Quirks::new().header(realm, params)
+}
+
+/// Percent encodes `input` as required by [RFC 5849 &sect; 3.6]
+/// (http://tools.ietf.org/html/rfc5849#section-3.6): every byte of its UTF-8 encoding
+/// except the unreserved characters `ALPHA`, `DIGIT`, `-`, `.`, `_` and `~` is
+/// replaced by `%XX`
+///
+/// ```
+/// use rust_oauth::oauth1::client::percent_encode;
+/// assert_eq!(percent_encode("Ladies + Gentlemen"), "Ladies%20%2B%20Gentlemen".to_string());
+/// ```
+pub fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for &b in input.as_bytes().iter() {
+        match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => out.push(b as char),
+            _ => out.push_str(format!("%{:02X}", b).as_slice())
+        }
+    }
+    out
+}
+
+/// Collects the OAuth protocol parameters shared by every request type, sorted by name.
+/// `others` holds the parameters specific to a request type, such as `oauth_token`,
+/// `oauth_callback` or `oauth_verifier`. The nonce and timestamp are left out for
+/// `PLAINTEXT`, as allowed by [RFC 5849 &sect; 3.1](http://tools.ietf.org/html/rfc5849#section-3.1)
+fn protocol_parameters(consumer_key: &str, signature_method: SignatureMethod, nonce: &str,
+                       timestamp: &str, signature: &str, version: Option<&str>,
+                       others: Vec<(&str, &str)>) -> Vec<(String, String)> {
+    let mut params = vec![("oauth_consumer_key", consumer_key), ("oauth_signature", signature)];
+    match signature_method {
+        SignatureMethod::PLAINTEXT  => (),
+        _                           => {
+            params.push(("oauth_nonce", nonce));
+            params.push(("oauth_timestamp", timestamp));
+        }
+    };
+    match version {
+        None => (),
+        Some(v) => params.push(("oauth_version", v)),
+    };
+    params.extend(others.into_iter());
+
+    let method = signature_method.to_string();
+    let mut params : Vec<(String, String)> = params.into_iter()
+                                                   .map(|(k, v)| (k.to_string(), v.to_string()))
+                                                   .collect();
+    params.push((String::from_str("oauth_signature_method"), method));
+    params.sort();
+    params
+}
+
+/// Returns the key used to sign requests, from the consumer and token secrets, as
+/// described in [RFC 5849 &sect; 3.4.2](http://tools.ietf.org/html/rfc5849#section-3.4.2).
+/// `token_secret` is empty when no token is used
+fn signing_key(consumer_secret: &str, token_secret: &str) -> String {
+    format!("{}&{}", percent_encode(consumer_secret), percent_encode(token_secret))
+}
+
+/// Percent encodes the protocol parameters for use in a base string, leaving out
+/// `oauth_signature`
+fn base_string_parameters(params: Vec<(String, String)>) -> Vec<String> {
+    params.into_iter()
+          .filter(|&(ref k, _)| k.as_slice() != "oauth_signature")
+          .map(|(k, v)| format!("{}={}", percent_encode(k.as_slice()), percent_encode(v.as_slice())))
+          .collect()
 }
 
 // TODO: add to crypto library?
-fn generate_nonce() -> String {
-    OsRng::new().unwrap()
-                .gen_ascii_chars()
-                .take(32)
-                .collect()
+fn generate_nonce(format: NonceFormat) -> Result<String, Error> {
+    let mut rng = try!(OsRng::new().map_err(|e| Error::Randomness(e.to_string())));
+    Ok(match format {
+        NonceFormat::Alphanumeric(len) => rng.gen_ascii_chars().take(len).collect(),
+        NonceFormat::Hex(len) => (0..len).map(|_| b"0123456789abcdef"[rng.gen_range(0, 16)] as char).collect(),
+    })
+}
+
+/// Returns the current time in `unit`, corrected by `offset` seconds to match the
+/// provider's clock
+fn generate_timestamp(offset: i64, unit: TimestampUnit) -> String {
+    let now = now_utc().to_timespec();
+    match unit {
+        TimestampUnit::Seconds => (now.sec + offset).to_string(),
+        TimestampUnit::Milliseconds => ((now.sec + offset) * 1000 + (now.nsec / 1000000) as i64).to_string(),
+    }
+}
+
+/// Format of the http `Date` header, as in [RFC 7231 &sect; 7.1.1.1]
+/// (http://tools.ietf.org/html/rfc7231#section-7.1.1.1)
+const HTTP_DATE : &'static str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Decides whether the request refused with `response` is signed again and sent once
+/// more, which only happens with `retry`. A refused timestamp also updates
+/// `clock_offset` from the provider's clock, so later requests are accepted; when the
+/// provider doesn't tell its time, the retry only gets a fresh nonce and timestamp
+fn retry_refused(response: &Response, clock_offset: &mut i64, retry: bool) -> bool {
+    match response.get_problem() {
+        Some(ref problem) => match *problem.get_kind() {
+            ProblemKind::TimestampRefused => {
+                match server_time(response) {
+                    Some(time) => *clock_offset = time - now_utc().to_timespec().sec,
+                    None => ()
+                };
+                retry
+            },
+            ProblemKind::NonceUsed => retry,
+            _ => false
+        },
+        None => false
+    }
 }
 
-fn generate_timestamp() -> String {
-    now_utc().to_timespec().sec.to_string()
+/// Returns the provider's current time in seconds, as told by `response`
+fn server_time(response: &Response) -> Option<i64> {
+    let acceptable = response.get_problem()
+                             .and_then(|p| match *p.get_kind() {
+                                 ProblemKind::TimestampRefused => p.get_acceptable_timestamps(),
+                                 _ => None
+                             });
+    match acceptable {
+        Some((min, max)) => Some(min + (max - min) / 2),
+        None => response.get_header("Date")
+                        .and_then(|date| strptime(date.trim(), HTTP_DATE).ok())
+                        .map(|tm| tm.to_timespec().sec)
+    }
 }
 
 pub trait BaseString {
     /// Returns a base string URI, ecnoded with [RFC3986]. This gets used to
     /// generate the `oauth_signature`. It takes a different path dependent
-    /// on the signature type
-    fn get_base_string(&self, method: HTTPMethod, base_url: &str, data: Vec<(&str, &str)>) -> String {
+    /// on the signature type. Fails with `Error::InvalidUrl` if `base_url` is not an
+    /// absolute URL
+    fn get_base_string(&self, method: HTTPMethod, base_url: &str, data: Vec<(&str, &str)>)
+                       -> Result<String, Error> {
+        if Url::parse(base_url).is_err() {
+            return Err(Error::InvalidUrl(base_url.to_string()));
+        }
         // split URL at `?`, to sort parameters
         let split_url : Vec<&str> = base_url.rsplitn(1, '?').collect();
         let (url, url_data) = match split_url.len() {
-            1 => (Some(split_url[0]), None),                // no parameters in the request url
-            2 => (Some(split_url[1]), Some(split_url[0])),  // if there are parameters
-            _ => (None, None)                               // erronous input base_url
+            1 => (split_url[0], None),                // no parameters in the request url
+            2 => (split_url[1], Some(split_url[0])),  // if there are parameters
+            _ => return Err(Error::InvalidUrl(base_url.to_string()))
         };
-        format!("{}&{}&{}", method,
-                utf8_percent_encode(url.unwrap(), FORM_URLENCODED_ENCODE_SET),
-                utf8_percent_encode(self.get_base_parameters(data, url_data).as_slice(), FORM_URLENCODED_ENCODE_SET))
+        // RFC 5849 3.4.1.1: the method is always signed in uppercase
+        Ok(format!("{}&{}&{}", method.to_string().to_ascii_uppercase(),
+                   utf8_percent_encode(url, FORM_URLENCODED_ENCODE_SET),
+                   utf8_percent_encode(self.get_base_parameters(data, url_data).as_slice(), FORM_URLENCODED_ENCODE_SET)))
     }
     /// Returns all the required parameters used in the OAuth request. It takes into account
     /// the signature method as well as which type of OAuth request you are making
@@ -110,7 +325,8 @@ pub fn concat(data: &[String], sep : &str) -> String {
 
 #[cfg(test)]
 mod test {
-    use super::{concat, generate_nonce};
+    use super::{concat, generate_nonce, generate_timestamp, percent_encode, authorization_header};
+    use super::quirks::{NonceFormat, TimestampUnit};
 
     #[test]
     fn concat_test_multiple_items() {
@@ -134,10 +350,66 @@ mod test {
     fn generate_nonce_unique(){
         let mut nonces = Vec::new();
         for _ in 0..1000 {
-            nonces.push(generate_nonce())
+            nonces.push(generate_nonce(NonceFormat::Alphanumeric(32)).unwrap())
         }
         let len = nonces.len();
         nonces.dedup();
         assert_eq!(len, nonces.len());
     }
+
+    #[test]
+    fn generate_nonce_formats() {
+        let nonce = generate_nonce(NonceFormat::Hex(40)).unwrap();
+        assert_eq!(nonce.len(), 40);
+        assert!(nonce.chars().all(|c| c.is_digit(16) && !c.is_uppercase()));
+        let nonce = generate_nonce(NonceFormat::Alphanumeric(8)).unwrap();
+        assert_eq!(nonce.len(), 8);
+        assert!(nonce.chars().all(|c| c.is_alphanumeric()));
+    }
+
+    #[test]
+    fn generate_timestamp_units() {
+        let seconds : i64 = generate_timestamp(0, TimestampUnit::Seconds).parse().unwrap();
+        let millis : i64 = generate_timestamp(0, TimestampUnit::Milliseconds).parse().unwrap();
+        assert!(millis / 1000 - seconds <= 1);
+        let skewed : i64 = generate_timestamp(-60, TimestampUnit::Seconds).parse().unwrap();
+        assert!(seconds - skewed >= 59);
+    }
+
+    #[test]
+    /// Test vectors from [RFC 3986 &sect; 2](http://tools.ietf.org/html/rfc3986#section-2)
+    fn percent_encode_test() {
+        assert_eq!(percent_encode("abcXYZ019-._~"), "abcXYZ019-._~".to_string());
+        assert_eq!(percent_encode("Hello Ladies + Gentlemen, a signed OAuth request!"),
+                   "Hello%20Ladies%20%2B%20Gentlemen%2C%20a%20signed%20OAuth%20request%21".to_string());
+        assert_eq!(percent_encode("*=&\"/"), "%2A%3D%26%22%2F".to_string());
+        assert_eq!(percent_encode("\u{e9}"), "%C3%A9".to_string());
+    }
+
+    #[test]
+    fn authorization_header_test() {
+        let params = vec![("oauth_callback".to_string(), "http://printer.example.com/ready".to_string()),
+                          ("oauth_verifier".to_string(), "hfdp7dh39dks9884".to_string())];
+        assert_eq!(authorization_header(None, params.as_slice()),
+                   "OAuth oauth_callback=\"http%3A%2F%2Fprinter.example.com%2Fready\", \
+                    oauth_verifier=\"hfdp7dh39dks9884\"".to_string());
+        assert_eq!(authorization_header(Some("Photos \"2015\""), &[]),
+                   "OAuth realm=\"Photos \\\"2015\\\"\"".to_string());
+    }
+
+    #[test]
+    fn base_string_invalid_url() {
+        use super::BaseString;
+        use error::Error;
+
+        struct NoParams;
+        impl BaseString for NoParams {
+            fn get_self_paramaters(&self) -> Vec<String> { Vec::new() }
+        }
+
+        match NoParams.get_base_string(super::HTTPMethod::GET, "not a url", vec![]) {
+            Err(Error::InvalidUrl(url)) => assert_eq!(url, "not a url".to_string()),
+            _ => panic!("expected Error::InvalidUrl")
+        }
+    }
 }
@@ -0,0 +1,354 @@
+//! Interprets the [OAuth Problem Reporting](http://wiki.oauth.net/w/page/12238543/ProblemReporting)
+//! extension. Providers rejecting a request describe why with `oauth_problem`, either
+//! in the `WWW-Authenticate` header or in a form-encoded body, optionally along with
+//! the acceptable timestamps or versions, the missing parameters and some advice.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::oauth1::client::problem::{Problem, ProblemKind};
+//! let header = "OAuth realm=\"Example\", oauth_problem=\"timestamp_refused\", \
+//!               oauth_acceptable_timestamps=\"1318622900-1318623000\"";
+//! let problem = Problem::from_header(header).unwrap();
+//! assert_eq!(*problem.get_kind(), ProblemKind::TimestampRefused);
+//! assert_eq!(problem.get_acceptable_timestamps(), Some((1318622900, 1318623000)));
+//!```
+
+use oauth1::client::concat;
+use oauth1::client::url::form_urlencoded;
+use oauth1::client::transport::Response;
+use std::ascii::AsciiExt;
+use std::fmt;
+
+/// Value of `oauth_problem`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProblemKind {
+    VersionRejected,
+    ParameterAbsent,
+    ParameterRejected,
+    TimestampRefused,
+    NonceUsed,
+    SignatureMethodRejected,
+    SignatureInvalid,
+    ConsumerKeyUnknown,
+    ConsumerKeyRejected,
+    ConsumerKeyRefused,
+    TokenUsed,
+    TokenExpired,
+    TokenRevoked,
+    TokenRejected,
+    AdditionalAuthorizationRequired,
+    PermissionUnknown,
+    PermissionDenied,
+    UserRefused,
+    /// A problem not defined by the extension
+    Other(String),
+}
+
+impl ProblemKind {
+    pub fn from_str(problem: &str) -> ProblemKind {
+        match problem {
+            "version_rejected" => ProblemKind::VersionRejected,
+            "parameter_absent" => ProblemKind::ParameterAbsent,
+            "parameter_rejected" => ProblemKind::ParameterRejected,
+            "timestamp_refused" => ProblemKind::TimestampRefused,
+            "nonce_used" => ProblemKind::NonceUsed,
+            "signature_method_rejected" => ProblemKind::SignatureMethodRejected,
+            "signature_invalid" => ProblemKind::SignatureInvalid,
+            "consumer_key_unknown" => ProblemKind::ConsumerKeyUnknown,
+            "consumer_key_rejected" => ProblemKind::ConsumerKeyRejected,
+            "consumer_key_refused" => ProblemKind::ConsumerKeyRefused,
+            "token_used" => ProblemKind::TokenUsed,
+            "token_expired" => ProblemKind::TokenExpired,
+            "token_revoked" => ProblemKind::TokenRevoked,
+            "token_rejected" => ProblemKind::TokenRejected,
+            "additional_authorization_required" => ProblemKind::AdditionalAuthorizationRequired,
+            "permission_unknown" => ProblemKind::PermissionUnknown,
+            "permission_denied" => ProblemKind::PermissionDenied,
+            "user_refused" => ProblemKind::UserRefused,
+            other => ProblemKind::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let out = match *self {
+            ProblemKind::VersionRejected => "version_rejected",
+            ProblemKind::ParameterAbsent => "parameter_absent",
+            ProblemKind::ParameterRejected => "parameter_rejected",
+            ProblemKind::TimestampRefused => "timestamp_refused",
+            ProblemKind::NonceUsed => "nonce_used",
+            ProblemKind::SignatureMethodRejected => "signature_method_rejected",
+            ProblemKind::SignatureInvalid => "signature_invalid",
+            ProblemKind::ConsumerKeyUnknown => "consumer_key_unknown",
+            ProblemKind::ConsumerKeyRejected => "consumer_key_rejected",
+            ProblemKind::ConsumerKeyRefused => "consumer_key_refused",
+            ProblemKind::TokenUsed => "token_used",
+            ProblemKind::TokenExpired => "token_expired",
+            ProblemKind::TokenRevoked => "token_revoked",
+            ProblemKind::TokenRejected => "token_rejected",
+            ProblemKind::AdditionalAuthorizationRequired => "additional_authorization_required",
+            ProblemKind::PermissionUnknown => "permission_unknown",
+            ProblemKind::PermissionDenied => "permission_denied",
+            ProblemKind::UserRefused => "user_refused",
+            ProblemKind::Other(ref p) => p.as_slice(),
+        };
+        write!(f, "{}", out)
+    }
+}
+
+/// A problem reported by the provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    kind : ProblemKind,
+    acceptable_versions : Option<(String, String)>,
+    acceptable_timestamps : Option<(i64, i64)>,
+    parameters_absent : Vec<String>,
+    parameters_rejected : Vec<String>,
+    advice : Option<String>,
+}
+
+impl Problem {
+    /// Reads the problem from decoded parameters, if `oauth_problem` is among them
+    pub fn from_parameters(params: &[(String, String)]) -> Option<Problem> {
+        let get = |name| get_parameter(params, name);
+        let kind = match get("oauth_problem") {
+            Some(p) => ProblemKind::from_str(p),
+            None => return None
+        };
+        Some(Problem {
+            kind : kind,
+            acceptable_versions : get("oauth_acceptable_versions").and_then(parse_range)
+                                      .map(|(a, b)| (a.to_string(), b.to_string())),
+            acceptable_timestamps : get("oauth_acceptable_timestamps").and_then(parse_range)
+                                        .and_then(|(a, b)| match (a.parse(), b.parse()) {
+                                            (Ok(a), Ok(b)) => Some((a, b)),
+                                            _ => None
+                                        }),
+            parameters_absent : get("oauth_parameters_absent").map(parse_list).unwrap_or(vec![]),
+            parameters_rejected : get("oauth_parameters_rejected").map(parse_list).unwrap_or(vec![]),
+            advice : get("oauth_problem_advice").map(|a| a.to_string()),
+        })
+    }
+
+    /// Reads the problem from a `WWW-Authenticate: OAuth ...` header
+    pub fn from_header(header: &str) -> Option<Problem> {
+        let header = header.trim();
+        if !header.to_ascii_lowercase().starts_with("oauth ") {
+            return None;
+        }
+        // the parameter values are percent encoded, as in a form body; the scheme is
+        // ascii, so slicing it off falls on a character boundary
+        let pairs : Vec<String> = parse_header_parameters(&header[6..])
+                                      .into_iter()
+                                      .map(|(key, value)| format!("{}={}", key, value.replace("&", "%26")
+                                                                                     .replace("+", "%2B")))
+                                      .collect();
+        Problem::from_body(concat(pairs.as_slice(), "&").as_bytes())
+    }
+
+    /// Reads the problem from a form-encoded body
+    pub fn from_body(body: &[u8]) -> Option<Problem> {
+        Problem::from_parameters(form_urlencoded::parse(body).as_slice())
+    }
+
+    /// Reads the problem from the `WWW-Authenticate` header of `response`, or from
+    /// its body
+    pub fn from_response(response: &Response) -> Option<Problem> {
+        response.get_header("WWW-Authenticate")
+                .and_then(Problem::from_header)
+                .or_else(|| Problem::from_body(response.get_body()))
+    }
+
+    pub fn get_kind(&self) -> &ProblemKind {
+        &self.kind
+    }
+
+    /// Returns `oauth_acceptable_versions`, as the lowest and highest versions accepted
+    pub fn get_acceptable_versions(&self) -> Option<(&str, &str)> {
+        self.acceptable_versions.as_ref().map(|&(ref a, ref b)| (a.as_slice(), b.as_slice()))
+    }
+
+    /// Returns `oauth_acceptable_timestamps`, as the oldest and most recent timestamps
+    /// accepted, in seconds
+    pub fn get_acceptable_timestamps(&self) -> Option<(i64, i64)> {
+        self.acceptable_timestamps
+    }
+
+    /// Returns the names listed in `oauth_parameters_absent`
+    pub fn get_parameters_absent(&self) -> &[String] {
+        self.parameters_absent.as_slice()
+    }
+
+    /// Returns the names listed in `oauth_parameters_rejected`
+    pub fn get_parameters_rejected(&self) -> &[String] {
+        self.parameters_rejected.as_slice()
+    }
+
+    /// Returns `oauth_problem_advice`, meant to be shown to the user
+    pub fn get_advice(&self) -> Option<&str> {
+        self.advice.as_ref().map(|a| a.as_slice())
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self.advice {
+            Some(ref advice) => write!(f, "{} ({})", self.kind, advice),
+            None => write!(f, "{}", self.kind)
+        }
+    }
+}
+
+fn get_parameter<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params.iter().find(|&&(ref k, _)| k.as_slice() == name).map(|&(_, ref v)| v.as_slice())
+}
+
+/// Splits the parameters of a `WWW-Authenticate` header, given as `name="value"` or
+/// `name=value` and separated by commas. Quoted values may hold commas and escaped
+/// quotes
+fn parse_header_parameters(params: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c == ',' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while chars.peek() == Some(&' ') {
+                chars.next();
+            }
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        None | Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => value.push(c),
+                            None => break
+                        },
+                        Some(c) => value.push(c)
+                    }
+                }
+                // anything between the closing quote and the next separator is dropped
+                while let Some(&c) = chars.peek() {
+                    if c == ',' {
+                        break;
+                    }
+                    chars.next();
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c == ',' {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                value = value.trim().to_string();
+            }
+        }
+        let name = name.trim();
+        if !name.is_empty() {
+            pairs.push((name.to_string(), value));
+        }
+        // the separator, if any
+        if chars.next().is_none() {
+            break;
+        }
+    }
+    pairs
+}
+
+/// Splits `min-max`
+fn parse_range(range: &str) -> Option<(&str, &str)> {
+    let mut split = range.splitn(1, '-');
+    match (split.next(), split.next()) {
+        (Some(a), Some(b)) => Some((a.trim(), b.trim())),
+        _ => None
+    }
+}
+
+/// Splits a list of parameter names, separated by `&`
+fn parse_list(list: &str) -> Vec<String> {
+    list.split('&').filter(|p| !p.is_empty()).map(|p| p.to_string()).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Problem, ProblemKind};
+    use oauth1::client::transport::Response;
+
+    #[test]
+    fn problem_from_header() {
+        let header = "OAuth realm=\"http://sp.example.com/\", oauth_problem=\"timestamp_refused\", \
+                      oauth_acceptable_timestamps=\"1318622900-1318623000\"";
+        let problem = Problem::from_header(header).unwrap();
+        assert_eq!(*problem.get_kind(), ProblemKind::TimestampRefused);
+        assert_eq!(problem.get_acceptable_timestamps(), Some((1318622900, 1318623000)));
+        assert!(problem.get_parameters_absent().is_empty());
+
+        assert_eq!(Problem::from_header("OAuth realm=\"Example\""), None);
+        assert_eq!(Problem::from_header("Basic realm=\"Example\""), None);
+    }
+
+    #[test]
+    fn problem_from_header_quoted_commas() {
+        let header = "OAuth realm=\"Photos, \\\"2015\\\"\", oauth_problem=token_rejected, \
+                      oauth_problem_advice=\"Sign in again, then retry\"";
+        let problem = Problem::from_header(header).unwrap();
+        assert_eq!(*problem.get_kind(), ProblemKind::TokenRejected);
+        assert_eq!(problem.get_advice(), Some("Sign in again, then retry"));
+    }
+
+    #[test]
+    fn problem_from_header_multibyte() {
+        assert_eq!(Problem::from_header("OAuth\u{e9}oauth_problem=\"nonce_used\""), None);
+        assert_eq!(Problem::from_header("\u{e9}\u{e9}\u{e9}"), None);
+    }
+
+    #[test]
+    fn problem_from_body() {
+        let body = b"oauth_problem=parameter_absent&oauth_parameters_absent=oauth_nonce%26oauth_timestamp\
+                     &oauth_problem_advice=Check%20your%20client";
+        let problem = Problem::from_body(body).unwrap();
+        assert_eq!(*problem.get_kind(), ProblemKind::ParameterAbsent);
+        assert_eq!(problem.get_parameters_absent(), ["oauth_nonce".to_string(), "oauth_timestamp".to_string()]);
+        assert_eq!(problem.get_advice(), Some("Check your client"));
+        assert_eq!(problem.to_string(), "parameter_absent (Check your client)".to_string());
+    }
+
+    #[test]
+    fn problem_kinds() {
+        assert_eq!(ProblemKind::from_str("nonce_used"), ProblemKind::NonceUsed);
+        assert_eq!(ProblemKind::from_str("signature_invalid"), ProblemKind::SignatureInvalid);
+        assert_eq!(ProblemKind::from_str("version_rejected"), ProblemKind::VersionRejected);
+        assert_eq!(ProblemKind::from_str("unheard_of"), ProblemKind::Other("unheard_of".to_string()));
+        assert_eq!(ProblemKind::TokenExpired.to_string(), "token_expired".to_string());
+
+        let problem = Problem::from_body(b"oauth_problem=version_rejected&oauth_acceptable_versions=1.0-1.0").unwrap();
+        assert_eq!(problem.get_acceptable_versions(), Some(("1.0", "1.0")));
+    }
+
+    #[test]
+    fn problem_from_response() {
+        let resp = Response::new(401, vec![("WWW-Authenticate".to_string(),
+                                            "OAuth oauth_problem=\"nonce_used\"".to_string())], vec![]);
+        assert_eq!(*Problem::from_response(&resp).unwrap().get_kind(), ProblemKind::NonceUsed);
+
+        let resp = Response::new(401, vec![], b"oauth_problem=signature_invalid".to_vec());
+        assert_eq!(*Problem::from_response(&resp).unwrap().get_kind(), ProblemKind::SignatureInvalid);
+
+        let resp = Response::new(401, vec![], b"Unauthorized".to_vec());
+        assert_eq!(Problem::from_response(&resp), None);
+    }
+}
@@ -0,0 +1,363 @@
+//! Presets for well known OAuth 1.0a providers: their endpoints, the signature method
+//! they expect, where they want the OAuth parameters of resource requests, and whether
+//! `oauth_version` should be sent. A `Registry` holds the presets by name, and your
+//! own providers can be registered alongside them.
+//!
+//! Magento and NetSuite endpoints depend on the store or account, so their presets
+//! are built with `magento` and `netsuite` rather than found in the registry.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::oauth1::client::providers::Registry;
+//!
+//! let registry = Registry::new();
+//! let twitter = registry.get("twitter").unwrap();
+//! let mut creds = twitter.temporary_credentials("key", "secret", "oob").create();
+//! let signed = creds.sign().unwrap();
+//! assert_eq!(signed.get_url(), "https://api.twitter.com/oauth/request_token");
+//! assert_eq!(twitter.authorize_url("hh5s93j4hdidpola"),
+//!            "https://api.twitter.com/oauth/authorize?oauth_token=hh5s93j4hdidpola".to_string());
+//!```
+
+use std::ascii::AsciiExt;
+use std::collections::HashMap;
+use std::default::Default;
+use oauth1::client::{Transmission, percent_encode};
+use oauth1::client::quirks::Quirks;
+use oauth1::client::session::Session;
+use oauth1::client::temporary_credentials;
+use oauth1::client::token_credentials;
+use crypto::SignatureMethod;
+
+/// Endpoints and conventions of an OAuth provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provider {
+    name : String,
+    request_token_url : String,
+    authorize_url : String,
+    access_token_url : String,
+    signature_method : SignatureMethod,
+    transmission : Transmission,
+    oauth_version : bool,
+    realm : Option<String>,
+    quirks : Quirks,
+}
+
+impl Provider {
+    /// Creates a provider signing with HMAC-SHA1, sending the OAuth parameters in the
+    /// `Authorization` header along with `oauth_version`
+    pub fn new(name: &str, request_token_url: &str, authorize_url: &str,
+               access_token_url: &str) -> Provider {
+        Provider {
+            name : name.to_string(),
+            request_token_url : request_token_url.to_string(),
+            authorize_url : authorize_url.to_string(),
+            access_token_url : access_token_url.to_string(),
+            signature_method : SignatureMethod::HMACSHA1,
+            transmission : Transmission::Header,
+            oauth_version : true,
+            realm : None,
+            quirks : Default::default(),
+        }
+    }
+
+    pub fn set_signature_method(mut self, signature_method: SignatureMethod) -> Provider {
+        self.signature_method = signature_method;
+        self
+    }
+
+    /// Chooses where the OAuth parameters of resource requests are placed
+    pub fn set_transmission(mut self, transmission: Transmission) -> Provider {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn set_oauth_version(mut self, oauth_version: bool) -> Provider {
+        self.oauth_version = oauth_version;
+        self
+    }
+
+    pub fn set_realm(mut self, realm: &str) -> Provider {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    /// Sets how the provider departs from the RFC, for all its requests
+    pub fn set_quirks(mut self, quirks: Quirks) -> Provider {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.name.as_slice()
+    }
+
+    pub fn get_request_token_url(&self) -> &str {
+        self.request_token_url.as_slice()
+    }
+
+    pub fn get_authorize_url(&self) -> &str {
+        self.authorize_url.as_slice()
+    }
+
+    pub fn get_access_token_url(&self) -> &str {
+        self.access_token_url.as_slice()
+    }
+
+    pub fn get_signature_method(&self) -> SignatureMethod {
+        self.signature_method
+    }
+
+    pub fn get_transmission(&self) -> Transmission {
+        self.transmission
+    }
+
+    pub fn get_oauth_version(&self) -> bool {
+        self.oauth_version
+    }
+
+    pub fn get_realm(&self) -> Option<&str> {
+        self.realm.as_ref().map(|r| r.as_slice())
+    }
+
+    pub fn get_quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// Returns the url to send the resource owner to, to authorize the temporary
+    /// credentials `token`
+    pub fn authorize_url(&self, token: &str) -> String {
+        let sep = if self.authorize_url.contains_char('?') { "&" } else { "?" };
+        format!("{}{}oauth_token={}", self.authorize_url, sep, percent_encode(token))
+    }
+
+    /// Like `authorize_url`, but also passes `callback_url`, as OAuth Core 1.0 providers
+    /// expect it at this step. See `temporary_credentials::Builder::legacy`
+    pub fn legacy_authorize_url(&self, token: &str, callback_url: &str) -> String {
+        format!("{}&oauth_callback={}", self.authorize_url(token), percent_encode(callback_url))
+    }
+
+    /// Starts a request for temporary credentials to this provider
+    pub fn temporary_credentials<'a>(&'a self, consumer_key: &'a str, consumer_secret: &'a str,
+                                     callback_url: &'a str) -> temporary_credentials::Builder<'a> {
+        let builder = temporary_credentials::Builder::new(self.get_request_token_url(), consumer_key,
+                                                          consumer_secret, callback_url, self.signature_method);
+        let builder = if self.oauth_version { builder.use_version() } else { builder };
+        let builder = builder.set_quirks(self.quirks.clone());
+        match self.realm {
+            Some(ref realm) => builder.set_realm(realm.as_slice()),
+            None => builder
+        }
+    }
+
+    /// Starts a request for token credentials to this provider
+    pub fn token_credentials<'a>(&'a self, consumer_key: &'a str, consumer_secret: &'a str,
+                                 token: &'a str, token_secret: &'a str,
+                                 verifier: &'a str) -> token_credentials::Builder<'a> {
+        let builder = token_credentials::Builder::new(self.get_access_token_url(), consumer_key, consumer_secret,
+                                                      token, token_secret, verifier, self.signature_method);
+        let builder = if self.oauth_version { builder.use_version() } else { builder };
+        let builder = builder.set_quirks(self.quirks.clone());
+        match self.realm {
+            Some(ref realm) => builder.set_realm(realm.as_slice()),
+            None => builder
+        }
+    }
+
+    /// Creates a `Session` for resource requests to this provider
+    pub fn session<'a>(&'a self, consumer_key: &'a str, consumer_secret: &'a str,
+                       token: &'a str, token_secret: &'a str) -> Session<'a> {
+        let session = Session::new(consumer_key, consumer_secret, token, token_secret, self.signature_method)
+                          .set_transmission(self.transmission)
+                          .set_oauth_version(self.oauth_version)
+                          .set_quirks(self.quirks.clone());
+        match self.realm {
+            Some(ref realm) => session.set_realm(realm.as_slice()),
+            None => session
+        }
+    }
+}
+
+pub fn twitter() -> Provider {
+    Provider::new("twitter", "https://api.twitter.com/oauth/request_token",
+                  "https://api.twitter.com/oauth/authorize", "https://api.twitter.com/oauth/access_token")
+}
+
+pub fn flickr() -> Provider {
+    Provider::new("flickr", "https://www.flickr.com/services/oauth/request_token",
+                  "https://www.flickr.com/services/oauth/authorize",
+                  "https://www.flickr.com/services/oauth/access_token")
+        .set_transmission(Transmission::Query)
+}
+
+pub fn tumblr() -> Provider {
+    Provider::new("tumblr", "https://www.tumblr.com/oauth/request_token",
+                  "https://www.tumblr.com/oauth/authorize", "https://www.tumblr.com/oauth/access_token")
+}
+
+pub fn trello() -> Provider {
+    Provider::new("trello", "https://trello.com/1/OAuthGetRequestToken",
+                  "https://trello.com/1/OAuthAuthorizeToken", "https://trello.com/1/OAuthGetAccessToken")
+}
+
+pub fn etsy() -> Provider {
+    Provider::new("etsy", "https://openapi.etsy.com/v2/oauth/request_token",
+                  "https://www.etsy.com/oauth/signin", "https://openapi.etsy.com/v2/oauth/access_token")
+}
+
+pub fn garmin() -> Provider {
+    Provider::new("garmin", "https://connectapi.garmin.com/oauth-service/oauth/request_token",
+                  "https://connect.garmin.com/oauthConfirm",
+                  "https://connectapi.garmin.com/oauth-service/oauth/access_token")
+}
+
+/// Xero public applications. Private and partner applications sign with RSA-SHA1,
+/// which is not supported yet
+pub fn xero() -> Provider {
+    Provider::new("xero", "https://api.xero.com/oauth/RequestToken",
+                  "https://api.xero.com/oauth/Authorize", "https://api.xero.com/oauth/AccessToken")
+}
+
+/// Evernote production service, which doesn't need `oauth_version`. OAuth only issues
+/// the token credentials: the NoteStore and UserStore are then called over Thrift,
+/// passing the token as the authentication token of each call rather than signing
+/// requests, so `Provider::session` is of no use with Evernote
+pub fn evernote() -> Provider {
+    Provider::new("evernote", "https://www.evernote.com/oauth",
+                  "https://www.evernote.com/OAuth.action", "https://www.evernote.com/oauth")
+        .set_oauth_version(false)
+}
+
+/// Magento 1 store at `store_url`, such as `https://shop.example.com`
+pub fn magento(store_url: &str) -> Provider {
+    let store_url = store_url.trim_right_matches('/');
+    Provider::new("magento", format!("{}/oauth/initiate", store_url).as_slice(),
+                  format!("{}/admin/oauth_authorize", store_url).as_slice(),
+                  format!("{}/oauth/token", store_url).as_slice())
+}
+
+/// NetSuite token-based authentication for `account_id`, which is also the realm.
+/// Requests are signed with HMAC-SHA256, which NetSuite requires
+pub fn netsuite(account_id: &str) -> Provider {
+    let host = account_id.to_ascii_lowercase().replace("_", "-");
+    Provider::new("netsuite",
+                  format!("https://{}.restlets.api.netsuite.com/rest/requesttoken", host).as_slice(),
+                  format!("https://{}.app.netsuite.com/app/login/secure/authorizetoken.nl", host).as_slice(),
+                  format!("https://{}.restlets.api.netsuite.com/rest/accesstoken", host).as_slice())
+        .set_realm(account_id)
+        .set_signature_method(SignatureMethod::HMACSHA256)
+}
+
+/// Providers by name
+#[derive(Clone, Debug)]
+pub struct Registry {
+    providers : HashMap<String, Provider>,
+}
+
+impl Registry {
+    /// Creates a registry holding the presets of this module whose endpoints don't
+    /// depend on an account
+    pub fn new() -> Registry {
+        let mut registry = Registry::empty();
+        for provider in vec![twitter(), flickr(), tumblr(), trello(), etsy(), garmin(), xero(),
+                             evernote()].into_iter() {
+            registry.register(provider);
+        }
+        registry
+    }
+
+    /// Creates a registry without any provider
+    pub fn empty() -> Registry {
+        Registry { providers : HashMap::new() }
+    }
+
+    /// Adds `provider` under its name, replacing any provider with the same name
+    pub fn register(&mut self, provider: Provider) {
+        self.providers.insert(provider.get_name().to_string(), provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provider> {
+        self.providers.get(name)
+    }
+
+    /// Returns the name of every registered provider
+    pub fn get_names(&self) -> Vec<&str> {
+        self.providers.keys().map(|k| k.as_slice()).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Provider, Registry, magento, netsuite};
+    use oauth1::client::{HTTPMethod, Transmission};
+    use crypto::SignatureMethod;
+
+    #[test]
+    fn registry_presets() {
+        let registry = Registry::new();
+        let mut names = registry.get_names();
+        names.sort();
+        assert_eq!(names, vec!["etsy", "evernote", "flickr", "garmin", "trello", "tumblr", "twitter", "xero"]);
+
+        let flickr = registry.get("flickr").unwrap();
+        assert_eq!(flickr.get_transmission(), Transmission::Query);
+        let evernote = registry.get("evernote").unwrap();
+        assert!(!evernote.get_oauth_version());
+        assert!(registry.get("myspace").is_none());
+    }
+
+    #[test]
+    fn registry_register() {
+        let mut registry = Registry::empty();
+        registry.register(Provider::new("example", "https://example.com/initiate",
+                                        "https://example.com/authorize?lang=en", "https://example.com/token")
+                              .set_signature_method(SignatureMethod::PLAINTEXT));
+        let example = registry.get("example").unwrap();
+        assert_eq!(example.get_signature_method(), SignatureMethod::PLAINTEXT);
+        assert_eq!(example.authorize_url("a/b"), "https://example.com/authorize?lang=en&oauth_token=a%2Fb".to_string());
+        assert_eq!(example.legacy_authorize_url("a", "http://client.example.com/ready"),
+                   "https://example.com/authorize?lang=en&oauth_token=a&\
+                    oauth_callback=http%3A%2F%2Fclient.example.com%2Fready".to_string());
+    }
+
+    #[test]
+    fn provider_requests() {
+        let registry = Registry::new();
+        let twitter = registry.get("twitter").unwrap();
+        let signed = twitter.token_credentials("key", "secret", "token", "token_secret", "verifier")
+                            .create().sign().unwrap();
+        assert_eq!(signed.get_url(), "https://api.twitter.com/oauth/access_token");
+        assert!(signed.get_header("Authorization").unwrap().contains("oauth_version=\"1.0\""));
+
+        let evernote = registry.get("evernote").unwrap();
+        let signed = evernote.token_credentials("key", "secret", "token", "token_secret", "verifier")
+                             .create().sign().unwrap();
+        assert_eq!(signed.get_url(), "https://www.evernote.com/oauth");
+        let header = signed.get_header("Authorization").unwrap();
+        assert!(header.contains("oauth_verifier=\"verifier\""));
+        assert!(!header.contains("oauth_version"));
+    }
+
+    #[test]
+    fn account_presets() {
+        let store = magento("https://shop.example.com/");
+        assert_eq!(store.get_request_token_url(), "https://shop.example.com/oauth/initiate");
+        assert_eq!(store.get_authorize_url(), "https://shop.example.com/admin/oauth_authorize");
+
+        let account = netsuite("123456_SB1");
+        assert_eq!(account.get_realm(), Some("123456_SB1"));
+        assert_eq!(account.get_access_token_url(), "https://123456-sb1.restlets.api.netsuite.com/rest/accesstoken");
+        assert_eq!(account.get_signature_method(), SignatureMethod::HMACSHA256);
+        let signed = account.session("key", "secret", "token", "token_secret")
+                            .sign(HTTPMethod::GET, "https://123456-sb1.restlets.api.netsuite.com/app/site/hosting/restlet.nl", vec![])
+                            .unwrap();
+        let header = signed.get_header("Authorization").unwrap();
+        assert!(header.starts_with("OAuth realm=\"123456_SB1\""));
+        assert!(header.contains("oauth_signature_method=\"HMAC-SHA256\""));
+        let expected = SignatureMethod::HMACSHA256.sign(signed.get_base_string().to_string(),
+                                                        String::from_str("secret&token_secret")).unwrap();
+        assert_eq!(signed.get_signature(), expected.as_slice());
+    }
+}
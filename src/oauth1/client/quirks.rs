@@ -0,0 +1,211 @@
+//! Deviations from RFC 5849 expected by some providers: the order, quoting and
+//! separator of the `Authorization` header fields, the format of the nonce, the unit
+//! of the timestamp and how `realm` is sent. The defaults follow the RFC.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::crypto::SignatureMethod;
+//! use rust_oauth::oauth1::client::quirks::{Quirks, NonceFormat, TimestampUnit, RealmHandling};
+//! use rust_oauth::oauth1::client::session::Session;
+//!
+//! let quirks = Quirks::new().set_nonce_format(NonceFormat::Hex(40))
+//!                           .set_timestamp_unit(TimestampUnit::Milliseconds)
+//!                           .set_realm_handling(RealmHandling::Omit);
+//! let s = Session::new("key", "secret", "token", "token_secret", SignatureMethod::HMACSHA1)
+//!             .set_quirks(quirks);
+//!```
+
+use std::default::Default;
+use oauth1::client::{concat, percent_encode};
+
+/// Order of the OAuth parameters in the `Authorization` header
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum HeaderOrder {
+    /// Sorted by name
+    Sorted,
+    /// The listed names first, in that order, then the others sorted by name
+    Fixed(Vec<String>),
+}
+
+/// Format of `oauth_nonce`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum NonceFormat {
+    /// That many random letters and digits
+    Alphanumeric(usize),
+    /// That many random lowercase hexadecimal digits
+    Hex(usize),
+}
+
+/// Unit of `oauth_timestamp`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum TimestampUnit {
+    Seconds,
+    Milliseconds,
+}
+
+/// How `realm` is sent in the `Authorization` header
+#[derive(Copy, Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum RealmHandling {
+    /// Sent when one is set
+    AsGiven,
+    /// Never sent, for providers rejecting it
+    Omit,
+    /// Always sent, empty when none is set
+    Always,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct Quirks {
+    header_order : HeaderOrder,
+    quoted : bool,
+    separator : String,
+    nonce_format : NonceFormat,
+    timestamp_unit : TimestampUnit,
+    realm_handling : RealmHandling,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            header_order : HeaderOrder::Sorted,
+            quoted : true,
+            separator : String::from_str(", "),
+            nonce_format : NonceFormat::Alphanumeric(32),
+            timestamp_unit : TimestampUnit::Seconds,
+            realm_handling : RealmHandling::AsGiven,
+        }
+    }
+}
+
+impl Quirks {
+    /// Creates the quirks of a provider following the RFC
+    pub fn new() -> Quirks {
+        Default::default()
+    }
+
+    pub fn set_header_order(mut self, header_order: HeaderOrder) -> Quirks {
+        self.header_order = header_order;
+        self
+    }
+
+    /// Chooses whether the header values are surrounded by double quotes, as the
+    /// RFC requires. Defaults to `true`. Without quotes, the realm is percent encoded
+    /// like the other values, as it could otherwise hold a separator
+    pub fn set_quoted(mut self, quoted: bool) -> Quirks {
+        self.quoted = quoted;
+        self
+    }
+
+    /// Sets the separator between the header fields. Defaults to `", "`
+    pub fn set_separator(mut self, separator: &str) -> Quirks {
+        self.separator = separator.to_string();
+        self
+    }
+
+    pub fn set_nonce_format(mut self, nonce_format: NonceFormat) -> Quirks {
+        self.nonce_format = nonce_format;
+        self
+    }
+
+    pub fn set_timestamp_unit(mut self, timestamp_unit: TimestampUnit) -> Quirks {
+        self.timestamp_unit = timestamp_unit;
+        self
+    }
+
+    pub fn set_realm_handling(mut self, realm_handling: RealmHandling) -> Quirks {
+        self.realm_handling = realm_handling;
+        self
+    }
+
+    pub fn get_header_order(&self) -> &HeaderOrder {
+        &self.header_order
+    }
+
+    pub fn get_nonce_format(&self) -> NonceFormat {
+        self.nonce_format
+    }
+
+    pub fn get_timestamp_unit(&self) -> TimestampUnit {
+        self.timestamp_unit
+    }
+
+    pub fn get_realm_handling(&self) -> RealmHandling {
+        self.realm_handling
+    }
+
+    /// Serializes the OAuth protocol parameters into the value of an `Authorization`
+    /// header. `authorization_header` uses the default quirks, which follow the RFC
+    pub fn header(&self, realm: Option<&str>, params: &[(String, String)]) -> String {
+        let quote = if self.quoted { "\"" } else { "" };
+        let realm = match self.realm_handling {
+            RealmHandling::AsGiven => realm,
+            RealmHandling::Omit => None,
+            RealmHandling::Always => Some(realm.unwrap_or("")),
+        };
+
+        let mut fields = Vec::new();
+        match realm {
+            None => (),
+            Some(r) if self.quoted => fields.push(format!("realm=\"{}\"",
+                                                          r.replace("\\", "\\\\").replace("\"", "\\\""))),
+            Some(r) => fields.push(format!("realm={}", percent_encode(r))),
+        };
+        let mut params : Vec<&(String, String)> = params.iter().collect();
+        match self.header_order {
+            HeaderOrder::Sorted => (),
+            HeaderOrder::Fixed(ref order) => {
+                let position = |name: &String| -> usize {
+                    order.iter().position(|o| o == name).unwrap_or(order.len())
+                };
+                // stable, so the parameters not listed stay sorted
+                params.sort_by(|&&(ref a, _), &&(ref b, _)| position(a).cmp(&position(b)));
+            }
+        };
+        for &&(ref key, ref value) in params.iter() {
+            fields.push(format!("{}={}{}{}", percent_encode(key.as_slice()), quote,
+                                percent_encode(value.as_slice()), quote));
+        }
+        format!("OAuth {}", concat(fields.as_slice(), self.separator.as_slice()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Quirks, HeaderOrder, RealmHandling};
+
+    fn params() -> Vec<(String, String)> {
+        vec![("oauth_consumer_key".to_string(), "key".to_string()),
+             ("oauth_nonce".to_string(), "abc".to_string()),
+             ("oauth_signature".to_string(), "si/g=".to_string()),
+             ("oauth_timestamp".to_string(), "137131201".to_string())]
+    }
+
+    #[test]
+    fn quirks_header_order() {
+        let quirks = Quirks::new().set_header_order(HeaderOrder::Fixed(vec!["oauth_signature".to_string(),
+                                                                            "oauth_timestamp".to_string()]));
+        assert_eq!(quirks.header(None, params().as_slice()),
+                   "OAuth oauth_signature=\"si%2Fg%3D\", oauth_timestamp=\"137131201\", \
+                    oauth_consumer_key=\"key\", oauth_nonce=\"abc\"".to_string());
+    }
+
+    #[test]
+    fn quirks_header_quoting() {
+        let quirks = Quirks::new().set_quoted(false).set_separator(",");
+        assert_eq!(quirks.header(Some("Photos"), &params()[..2]),
+                   "OAuth realm=Photos,oauth_consumer_key=key,oauth_nonce=abc".to_string());
+        // an unquoted realm can't hold a separator, a quote or a backslash as is
+        assert_eq!(quirks.header(Some("Photos, \"2015\" \\ a"), &params()[..1]),
+                   "OAuth realm=Photos%2C%20%222015%22%20%5C%20a,oauth_consumer_key=key".to_string());
+    }
+
+    #[test]
+    fn quirks_realm_handling() {
+        let omit = Quirks::new().set_realm_handling(RealmHandling::Omit);
+        assert_eq!(omit.header(Some("Photos"), &params()[..1]), "OAuth oauth_consumer_key=\"key\"".to_string());
+        let always = Quirks::new().set_realm_handling(RealmHandling::Always);
+        assert_eq!(always.header(None, &params()[..1]), "OAuth realm=\"\", oauth_consumer_key=\"key\"".to_string());
+    }
+}
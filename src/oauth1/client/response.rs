@@ -0,0 +1,266 @@
+//! Parses the `application/x-www-form-urlencoded` bodies returned by the temporary
+//! and token credential endpoints, as described in [RFC 5849 &sect; 2.1]
+//! (http://tools.ietf.org/html/rfc5849#section-2.1) and [&sect; 2.3]
+//! (http://tools.ietf.org/html/rfc5849#section-2.3)
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::oauth1::client::response::CredentialsResponse;
+//! let body = "oauth_token=hh5s93j4hdidpola&oauth_token_secret=hdhd0244k9j7ao03&user_id=42";
+//! let resp = CredentialsResponse::parse(Some("application/x-www-form-urlencoded"),
+//!                                       body.as_bytes()).unwrap();
+//! assert_eq!(resp.get_token(), "hh5s93j4hdidpola");
+//! assert_eq!(resp.get("user_id"), Some("42"));
+//!```
+
+use oauth1::client::url::form_urlencoded;
+use oauth1::client::transport::Response;
+use std::ascii::AsciiExt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::{fmt, str};
+
+/// Content type mandated by RFC 5849 for credential responses
+pub const FORM_URLENCODED : &'static str = "application/x-www-form-urlencoded";
+
+/// Content types accepted in addition to `FORM_URLENCODED`. Several providers
+/// (Twitter among them) label their credential responses as plain text or html
+const LENIENT_CONTENT_TYPES : [&'static str; 2] = ["text/plain", "text/html"];
+
+/// Errors raised while decoding a credential response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResponseError {
+    /// The response was labelled with a content type that cannot hold credentials
+    UnexpectedContentType(String),
+    /// The body was not valid UTF-8
+    InvalidEncoding,
+    /// `oauth_token` was absent or empty
+    MissingToken,
+    /// `oauth_token_secret` was absent
+    MissingTokenSecret,
+    /// `oauth_callback_confirmed` was present but not `true` or `false`
+    InvalidCallbackConfirmed(String),
+    /// The temporary credentials response did not confirm the callback with
+    /// `oauth_callback_confirmed=true`
+    CallbackNotConfirmed,
+    /// The provider answered with a status code other than `2xx`
+    UnexpectedStatus(u32),
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResponseError::UnexpectedContentType(ref c) =>
+                write!(f, "unexpected content type `{}` in credential response", c),
+            ResponseError::InvalidCallbackConfirmed(ref v) =>
+                write!(f, "invalid oauth_callback_confirmed value `{}`", v),
+            ResponseError::UnexpectedStatus(status) =>
+                write!(f, "provider answered with status {}", status),
+            _ => write!(f, "{}", self.description())
+        }
+    }
+}
+
+impl Error for ResponseError {
+    fn description(&self) -> &str {
+        match *self {
+            ResponseError::UnexpectedContentType(_) => "unexpected content type in credential response",
+            ResponseError::InvalidEncoding => "credential response is not valid UTF-8",
+            ResponseError::MissingToken => "credential response is missing oauth_token",
+            ResponseError::MissingTokenSecret => "credential response is missing oauth_token_secret",
+            ResponseError::InvalidCallbackConfirmed(_) => "invalid oauth_callback_confirmed value",
+            ResponseError::CallbackNotConfirmed => "provider did not confirm the callback",
+            ResponseError::UnexpectedStatus(_) => "provider answered with an unexpected status",
+        }
+    }
+}
+
+/// Decoded body of a temporary or token credential response. The required
+/// parameters are checked, and every other parameter the provider sent
+/// (`user_id`, `screen_name`, `xoauth_token_expiration`, ...) is kept as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CredentialsResponse {
+    oauth_token : String,
+    oauth_token_secret : String,
+    oauth_callback_confirmed : Option<bool>,
+    extra : HashMap<String, String>,
+}
+
+impl CredentialsResponse {
+    /// Parses `body`, checking `content_type` first when the transport provides it.
+    /// Parameters such as `charset` in the content type are ignored.
+    pub fn parse(content_type: Option<&str>, body: &[u8]) -> Result<CredentialsResponse, ResponseError> {
+        match content_type {
+            None => (),
+            Some(c) => try!(check_content_type(c)),
+        };
+        if str::from_utf8(body).is_err() {
+            return Err(ResponseError::InvalidEncoding);
+        }
+
+        let mut extra = HashMap::new();
+        for (key, value) in form_urlencoded::parse(body).into_iter() {
+            extra.insert(key, value);
+        }
+
+        let token = match extra.remove("oauth_token") {
+            Some(ref t) if t.is_empty() => return Err(ResponseError::MissingToken),
+            Some(t) => t,
+            None => return Err(ResponseError::MissingToken),
+        };
+        let token_secret = match extra.remove("oauth_token_secret") {
+            Some(s) => s,
+            None => return Err(ResponseError::MissingTokenSecret),
+        };
+        let confirmed = match extra.remove("oauth_callback_confirmed") {
+            None => None,
+            Some(ref v) if v.as_slice() == "true" => Some(true),
+            Some(ref v) if v.as_slice() == "false" => Some(false),
+            Some(v) => return Err(ResponseError::InvalidCallbackConfirmed(v)),
+        };
+
+        Ok(CredentialsResponse {
+            oauth_token : token,
+            oauth_token_secret : token_secret,
+            oauth_callback_confirmed : confirmed,
+            extra : extra,
+        })
+    }
+
+    /// Parses the response of a credential endpoint, failing if its status is not `2xx`
+    pub fn from_response(response: &Response) -> Result<CredentialsResponse, ResponseError> {
+        if !response.is_success() {
+            return Err(ResponseError::UnexpectedStatus(response.get_status()));
+        }
+        CredentialsResponse::parse(response.get_content_type(), response.get_body())
+    }
+
+    /// Returns `oauth_token`
+    pub fn get_token(&self) -> &str {
+        self.oauth_token.as_slice()
+    }
+
+    /// Returns `oauth_token_secret`
+    pub fn get_token_secret(&self) -> &str {
+        self.oauth_token_secret.as_slice()
+    }
+
+    /// Returns `oauth_callback_confirmed`, if the provider sent it. Only temporary
+    /// credential responses carry this parameter
+    pub fn get_callback_confirmed(&self) -> Option<bool> {
+        self.oauth_callback_confirmed
+    }
+
+    /// Returns `oauth_session_handle`, used to renew the token credentials with the
+    /// OAuth Session Extension
+    pub fn get_session_handle(&self) -> Option<&str> {
+        self.get("oauth_session_handle")
+    }
+
+    /// Returns `oauth_expires_in`, the lifetime of the token in seconds
+    pub fn get_expires_in(&self) -> Option<i64> {
+        self.get("oauth_expires_in").and_then(|e| e.parse().ok())
+    }
+
+    /// Returns `oauth_authorization_expires_in`, the time in seconds during which the
+    /// token credentials can be renewed
+    pub fn get_authorization_expires_in(&self) -> Option<i64> {
+        self.get("oauth_authorization_expires_in").and_then(|e| e.parse().ok())
+    }
+
+    /// Returns a provider specific parameter
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.extra.get(key).map(|v| v.as_slice())
+    }
+
+    /// Returns all provider specific parameters
+    pub fn get_extra(&self) -> &HashMap<String, String> {
+        &self.extra
+    }
+}
+
+fn check_content_type(content_type: &str) -> Result<(), ResponseError> {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    if mime.as_slice() == FORM_URLENCODED || LENIENT_CONTENT_TYPES.contains(&mime.as_slice()) {
+        Ok(())
+    } else {
+        Err(ResponseError::UnexpectedContentType(content_type.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{CredentialsResponse, ResponseError};
+    use oauth1::client::transport::Response;
+
+    #[test]
+    /// Example response from [RFC 5849 &sect; 2.1](http://tools.ietf.org/html/rfc5849#section-2.1)
+    fn parse_temporary_credentials_rfc() {
+        let body = "oauth_token=hh5s93j4hdidpola&oauth_token_secret=hdhd0244k9j7ao03&oauth_callback_confirmed=true";
+        let resp = CredentialsResponse::parse(Some("application/x-www-form-urlencoded"), body.as_bytes()).unwrap();
+        assert_eq!(resp.get_token(), "hh5s93j4hdidpola");
+        assert_eq!(resp.get_token_secret(), "hdhd0244k9j7ao03");
+        assert_eq!(resp.get_callback_confirmed(), Some(true));
+        assert!(resp.get_extra().is_empty());
+    }
+
+    #[test]
+    fn parse_keeps_extra_parameters() {
+        let body = "oauth_token=abc&oauth_token_secret=def&user_id=6253282&screen_name=twitter%20api&xoauth_token_expiration=3600";
+        let resp = CredentialsResponse::parse(Some("text/html; charset=utf-8"), body.as_bytes()).unwrap();
+        assert_eq!(resp.get_callback_confirmed(), None);
+        assert_eq!(resp.get("user_id"), Some("6253282"));
+        assert_eq!(resp.get("screen_name"), Some("twitter api"));
+        assert_eq!(resp.get("xoauth_token_expiration"), Some("3600"));
+        assert_eq!(resp.get("oauth_token"), None);
+    }
+
+    #[test]
+    fn parse_session_extension() {
+        let body = "oauth_token=abc&oauth_token_secret=def&oauth_expires_in=3600&\
+                    oauth_session_handle=AJ9m8fo.ZXyd&oauth_authorization_expires_in=a";
+        let resp = CredentialsResponse::parse(None, body.as_bytes()).unwrap();
+        assert_eq!(resp.get_session_handle(), Some("AJ9m8fo.ZXyd"));
+        assert_eq!(resp.get_expires_in(), Some(3600));
+        assert_eq!(resp.get_authorization_expires_in(), None);
+    }
+
+    #[test]
+    fn parse_missing_token() {
+        let body = "oauth_token_secret=def";
+        assert_eq!(CredentialsResponse::parse(None, body.as_bytes()), Err(ResponseError::MissingToken));
+        let body = "oauth_token=&oauth_token_secret=def";
+        assert_eq!(CredentialsResponse::parse(None, body.as_bytes()), Err(ResponseError::MissingToken));
+    }
+
+    #[test]
+    fn parse_missing_token_secret() {
+        let body = "oauth_token=abc";
+        assert_eq!(CredentialsResponse::parse(None, body.as_bytes()), Err(ResponseError::MissingTokenSecret));
+    }
+
+    #[test]
+    fn parse_unexpected_content_type() {
+        let body = "{\"oauth_token\": \"abc\"}";
+        assert_eq!(CredentialsResponse::parse(Some("application/json"), body.as_bytes()),
+                   Err(ResponseError::UnexpectedContentType("application/json".to_string())));
+    }
+
+    #[test]
+    fn parse_invalid_utf8() {
+        let body = [0x6fu8, 0xffu8, 0xfeu8];
+        assert_eq!(CredentialsResponse::parse(None, &body), Err(ResponseError::InvalidEncoding));
+    }
+
+    #[test]
+    fn from_response_checks_status() {
+        let body = b"oauth_token=abc&oauth_token_secret=def".to_vec();
+        let resp = Response::new(401, vec![], body.clone());
+        assert_eq!(CredentialsResponse::from_response(&resp), Err(ResponseError::UnexpectedStatus(401)));
+
+        let resp = Response::new(200, vec![("content-type".to_string(), "text/plain".to_string())], body);
+        assert_eq!(CredentialsResponse::from_response(&resp).unwrap().get_token(), "abc");
+    }
+}
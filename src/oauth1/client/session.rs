@@ -4,21 +4,28 @@
 //!
 //! TODO
 
-use oauth1::client::url::{FORM_URLENCODED_ENCODE_SET, utf8_percent_encode};
 use std::default::Default;
-use oauth1::client::{HTTPMethod, AuthorizationHeader, generate_nonce, generate_timestamp};
+use oauth1::client::{HTTPMethod, AuthorizationHeader, Transmission, signing_key, has_body,
+                     protocol_parameters, base_string_parameters, generate_nonce, generate_timestamp,
+                     retry_refused, server_time};
+use oauth1::client::signed_request::SignedRequest;
+use oauth1::client::transport::{Response, Transport};
+use oauth1::client::async_transport::{self, AsyncTransport, Pending};
+use oauth1::client::credentials::{ConsumerCredentials, TokenPair, OwnedSession};
+use oauth1::client::quirks::Quirks;
+use oauth1::client::time::now_utc;
 use crypto::SignatureMethod;
+use error::Error;
 
-macro_rules! encode(($inp : expr ) => (
-        utf8_percent_encode($inp, FORM_URLENCODED_ENCODE_SET)
-    );
-);
-
+/// Signs requests with borrowed credentials. Signing writes the nonce, timestamp and
+/// signature of the latest request into the session, so it takes `&mut self`; to sign
+/// from several threads at once, share a `credentials::OwnedSession` with an `Arc` instead
+#[derive(Clone)]
 #[unstable]
-pub struct Session<'a, CbRet> {
+pub struct Session<'a> {
     oauth_consumer_key : &'a str,
     oauth_consumer_secret : &'a str,
-    oauth_token : &'a str,
+    oauth_token : Option<&'a str>,
     oauth_token_secret : &'a str,
     realm : Option<&'a str>,
     oauth_signature_method : SignatureMethod,
@@ -26,21 +33,23 @@ pub struct Session<'a, CbRet> {
     oauth_timestamp : String,
     oauth_nonce : String,
     oauth_version : bool,
-    callback : fn(Session<CbRet>, HTTPMethod, &str, Vec<(&str, &str)>) -> CbRet,
+    transmission : Transmission,
+    clock_offset : i64,
+    retry : bool,
+    quirks : Quirks,
 }
 
 
-impl<'a, CbRet> Session<'a, CbRet> {
+impl<'a> Session<'a> {
     /// Creates a Session Object, which contains all reused parameters
     /// for OAuth 1.0A. This is the Struct used to communicate with a server
     pub fn new (consumer_key: &'a str, consumer_secret: &'a str, token: &'a str,
-                token_secret: &'a str, signature_method: SignatureMethod,
-                cb: fn(Session<CbRet>, HTTPMethod, &str, Vec<(&str, &str)>) -> CbRet)
-                -> Session<'a, CbRet> {
+                token_secret: &'a str, signature_method: SignatureMethod)
+                -> Session<'a> {
         Session {
             oauth_consumer_key: consumer_key,
             oauth_consumer_secret: consumer_secret,
-            oauth_token: token,
+            oauth_token: Some(token),
             oauth_token_secret: token_secret,
             oauth_signature_method: signature_method,
             oauth_signature: Default::default(),
@@ -48,95 +57,184 @@ impl<'a, CbRet> Session<'a, CbRet> {
             oauth_nonce: Default::default(),
             realm : None,
             oauth_version : true,
-            callback : cb,
+            transmission : Default::default(),
+            clock_offset : 0,
+            retry : false,
+            quirks : Default::default(),
         }
     }
 
+    /// Creates a two-legged Session, which signs with the consumer credentials only.
+    /// `oauth_token` is left out of requests, and the signing key is `consumer_secret&`
+    pub fn consumer_only(consumer_key: &'a str, consumer_secret: &'a str,
+                         signature_method: SignatureMethod) -> Session<'a> {
+        let mut session = Session::new(consumer_key, consumer_secret, "", "", signature_method);
+        session.oauth_token = None;
+        session
+    }
+
+    /// Whether the session signs without a token, see `consumer_only`
+    pub fn is_consumer_only(&self) -> bool {
+        self.oauth_token.is_none()
+    }
+
     pub fn set_realm(mut self, realm: &'a str) -> Self {
         self.realm = Some(realm);
         self
     }
 
+    /// Chooses where the OAuth parameters are placed in requests. Defaults to
+    /// `Transmission::Header`
+    pub fn set_transmission(mut self, transmission: Transmission) -> Self {
+        self.transmission = transmission;
+        self
+    }
 
-    /// Takes an API url, data, and HTTP Method and a closure and generates all needed
-    /// OAuth parameters and sends an HTTP request using the provided closure
-    pub fn request(&mut self, method: HTTPMethod, base_url: &str,
-                        data: Vec<(&str, &str)>) -> CbRet {
-        use oauth1::client::BaseString;
-        self.oauth_timestamp = generate_timestamp();
-        self.oauth_nonce = generate_nonce();
-        let base_string = self.get_base_string(HTTPMethod::GET, base_url, data.clone());
-        self.oauth_signature = self.generate_signature(base_string);
-        (self.callback)(self.clone(), method, base_url, data)
+    pub fn get_transmission(&self) -> Transmission {
+        self.transmission
     }
 
-    pub fn generate_signature(&mut self, base_string: String) -> String {
-        let key = format!("{}&{}", encode!(self.oauth_consumer_secret), encode!(self.oauth_token_secret));
-        encode!(self.oauth_signature_method.sign(base_string, key).as_slice())
+    /// Chooses whether `oauth_version=1.0` is sent, which the protocol makes optional.
+    /// It is sent by default
+    pub fn set_oauth_version(mut self, oauth_version: bool) -> Self {
+        self.oauth_version = oauth_version;
+        self
     }
-}
 
+    /// Follows `quirks` when generating nonces and timestamps and serializing the header
+    pub fn set_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Adds `offset` seconds to the timestamps of later requests, to make up for a
+    /// local clock which is off
+    pub fn set_clock_offset(mut self, offset: i64) -> Self {
+        self.clock_offset = offset;
+        self
+    }
 
-/// Creates a URL encoded String containing headers formated for an OAuth request
-impl<'a, CbRet> AuthorizationHeader for Session<'a, CbRet> {
-    fn get_header(&self) -> String {
-        let header = format!("OAuth {}oauth_consumer_key=\"{}\", \
-                              oauth_signature=\"{}\", oauth_signature_method=\"{}\", \
-                              oauth_token=\"{}\", oauth_version=\"1.0\"",
-                              match self.realm {
-                                  None => Default::default(),
-                                  Some(r) => format!("realm=\"{}\", ", r)
-                              },
-                              self.oauth_consumer_key, self.oauth_signature,
-                              self.oauth_signature_method, self.oauth_token
-                     );
-
-        match self.oauth_signature_method {
-            SignatureMethod::PLAINTEXT => header,
-            _ => format!("{}, oauth_timestamp=\"{}\", oauth_nonce=\"{}\"",
-                         header, self.oauth_timestamp, self.oauth_nonce)
+    pub fn get_clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+
+    /// Makes `request` re-sign and send the request once more when the provider
+    /// refuses it with `timestamp_refused` or `nonce_used`
+    pub fn set_retry(mut self, retry: bool) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Learns the difference between the provider's clock and the local one from
+    /// `response`: the middle of `oauth_acceptable_timestamps` when the provider
+    /// refused the timestamp, or else the `Date` header. Returns whether the clock
+    /// offset was updated
+    pub fn learn_clock_offset(&mut self, response: &Response) -> bool {
+        match server_time(response) {
+            Some(time) => {
+                self.clock_offset = time - now_utc().to_timespec().sec;
+                true
+            },
+            None => false
         }
     }
-}
 
-impl<'a, CbRet>  Clone for Session<'a, CbRet>  {
-    fn clone(&self) -> Self {
-        Session {
-            oauth_consumer_key: self.oauth_consumer_key,
-            oauth_consumer_secret: self.oauth_consumer_secret,
-            oauth_token: self.oauth_token,
-            oauth_token_secret: self.oauth_token_secret,
-            oauth_signature_method: self.oauth_signature_method,
-            oauth_signature: self.oauth_signature.clone(),
-            oauth_timestamp: self.oauth_timestamp.clone(),
-            oauth_nonce: self.oauth_nonce.clone(),
-            realm : self.realm,
-            oauth_version : self.oauth_version,
-            callback: self.callback,
+    /// Copies the credentials and configuration into an `OwnedSession`, which can
+    /// outlive them
+    pub fn to_owned_session(&self) -> OwnedSession {
+        let token = self.oauth_token.map(|token| TokenPair::new(token, self.oauth_token_secret));
+        let owned = OwnedSession::new(ConsumerCredentials::new(self.oauth_consumer_key,
+                                                               self.oauth_consumer_secret),
+                                      token, self.oauth_signature_method)
+                        .set_transmission(self.transmission)
+                        .set_oauth_version(self.oauth_version)
+                        .set_quirks(self.quirks.clone())
+                        .set_clock_offset(self.clock_offset)
+                        .set_retry(self.retry);
+        match self.realm {
+            Some(realm) => owned.set_realm(realm),
+            None => owned
         }
     }
-}
 
 
-impl <'a, CbRet> super::BaseString for Session<'a, CbRet> {
-    fn get_self_paramaters(&self) ->  Vec<String>{
-        let mut params = Vec::new();
+    /// Generates all needed OAuth parameters for a request and signs it, without
+    /// sending it. `data` must be percent encoded. The returned `SignedRequest` holds
+    /// the final url, body and headers, with the OAuth parameters placed according
+    /// to the session's `Transmission`. `Transmission::Body` fails with
+    /// `Error::UnsupportedTransmission` for methods without a body
+    pub fn sign(&mut self, method: HTTPMethod, base_url: &str,
+                data: Vec<(&str, &str)>) -> Result<SignedRequest, Error> {
+        use oauth1::client::BaseString;
+        if self.transmission == Transmission::Body && !has_body(&method) {
+            return Err(Error::UnsupportedTransmission(self.transmission));
+        }
+        self.oauth_timestamp = generate_timestamp(self.clock_offset, self.quirks.get_timestamp_unit());
+        self.oauth_nonce = try!(generate_nonce(self.quirks.get_nonce_format()));
+        let base_string = try!(self.get_base_string(method.clone(), base_url, data.clone()));
+        self.oauth_signature = try!(self.generate_signature(base_string.clone()));
+        Ok(SignedRequest::with_quirks(method, base_url, data, self.transmission, self.realm,
+                                      self.get_oauth_parameters(), base_string, &self.quirks))
+    }
 
-        match self.oauth_signature_method {
-            SignatureMethod::PLAINTEXT  => (),
-            _                           => {
-                params.push(format!("oauth_timestamp={}", self.oauth_timestamp));
-                params.push(format!("oauth_nonce={}", self.oauth_nonce));
-            }
+    /// Takes an API url, data, and HTTP Method, generates all needed OAuth
+    /// parameters and sends the signed request with `transport`. If the provider
+    /// refuses the timestamp, the clock offset is learned from its response; with
+    /// `set_retry`, the request is then signed again and sent once more
+    pub fn request<T: Transport>(&mut self, transport: &T, method: HTTPMethod, base_url: &str,
+                                 data: Vec<(&str, &str)>) -> Result<Response, Error> {
+        let signed = try!(self.sign(method.clone(), base_url, data.clone()));
+        let response = try!(transport.send(&signed));
+        if !retry_refused(&response, &mut self.clock_offset, self.retry) {
+            return Ok(response);
+        }
+        let signed = try!(self.sign(method, base_url, data));
+        transport.send(&signed)
+    }
+
+    /// Like `request`, but hands the signed request to an `AsyncTransport` and returns
+    /// without waiting for the response. Signing errors are returned immediately
+    pub fn request_async<T: AsyncTransport>(&mut self, transport: &T, method: HTTPMethod,
+                                            base_url: &str, data: Vec<(&str, &str)>)
+                                            -> Result<Pending<Response>, Error> {
+        let signed = try!(self.sign(method, base_url, data));
+        Ok(async_transport::send(transport, signed, async_transport::identity))
+    }
+
+    /// Signs `base_string` with the consumer and token secrets, returning the
+    /// unencoded `oauth_signature`
+    pub fn generate_signature(&mut self, base_string: String) -> Result<String, Error> {
+        let key = signing_key(self.oauth_consumer_secret, self.oauth_token_secret);
+        self.oauth_signature_method.sign(base_string, key)
+    }
+}
+
+
+impl<'a> AuthorizationHeader for Session<'a> {
+    fn get_oauth_parameters(&self) -> Vec<(String, String)> {
+        let token = match self.oauth_token {
+            Some(token) => vec![("oauth_token", token)],
+            None => vec![]
         };
+        protocol_parameters(self.oauth_consumer_key, self.oauth_signature_method,
+                            self.oauth_nonce.as_slice(), self.oauth_timestamp.as_slice(),
+                            self.oauth_signature.as_slice(),
+                            if self.oauth_version { Some("1.0") } else { None },
+                            token)
+    }
 
-        params.push(format!("oauth_consumer_key={}", self.oauth_consumer_key));
-        params.push(format!("oauth_signature_method={}", self.oauth_signature_method));
-        params.push(format!("oauth_token={}", self.oauth_token));
-        if self.oauth_version {
-            params.push(String::from_str("oauth_version=1.0"));
-        }
-        params
+    fn get_realm(&self) -> Option<&str> {
+        self.realm
+    }
+
+    fn get_quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+}
+
+impl <'a> super::BaseString for Session<'a> {
+    fn get_self_paramaters(&self) ->  Vec<String>{
+        base_string_parameters(self.get_oauth_parameters())
     }
 }
 
@@ -144,14 +242,20 @@ impl <'a, CbRet> super::BaseString for Session<'a, CbRet> {
 #[cfg(test)]
 mod tests {
     use super::Session;
-    use oauth1::client::{HTTPMethod, AuthorizationHeader, BaseString};
-    use oauth1::client::url::{FORM_URLENCODED_ENCODE_SET, utf8_percent_encode};
+    use oauth1::client::{HTTPMethod, AuthorizationHeader, BaseString, Transmission, percent_encode};
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::transport::Response;
+    use oauth1::client::problem::ProblemKind;
+    use oauth1::client::quirks::{Quirks, NonceFormat, TimestampUnit, RealmHandling};
+    use error::Error;
     use crypto::SignatureMethod;
 
-    #[allow(unused_variables)]
-    fn test_callback(session: Session<bool>, method: HTTPMethod,
-                     url: &str, data: Vec<(&str, &str)>) -> bool {
-        false
+    fn url_transport(request: &SignedRequest) -> Result<Response, Error> {
+        Ok(Response::new(200, vec![], request.get_url().as_bytes().to_vec()))
+    }
+
+    fn body_transport(request: &SignedRequest) -> Result<Response, Error> {
+        Ok(Response::new(200, vec![], request.get_body().unwrap_or("").as_bytes().to_vec()))
     }
 
     #[test]
@@ -159,10 +263,10 @@ mod tests {
     /// which can be [found here](https://dev.twitter.com/oauth/tools/signature-generator/4128189?nid=731)
     fn base_string_twitter_test() {
         let expected_base_string = "GET&https%3A%2F%2Fapi.twitter.com%2F1.1%2Fstatuses%2Fuser_timeline.json&count%3D2%26oauth_consumer_key%3Dk0azC44q2c0DgF7ua9YZ6Q%26oauth_nonce%3Db9114cda0b95170ff9b164d8226c4b07%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D1425071144%26oauth_token%3D119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj%26oauth_version%3D1.0%26screen_name%3Dtwitterapi";
-        let s = Session::<bool> {
+        let s = Session {
             oauth_consumer_key: "k0azC44q2c0DgF7ua9YZ6Q",
             oauth_consumer_secret: "omqK3feYaKOBgZajh7pqe5AU7oDkmTjLtf1p08ro1M",
-            oauth_token: "119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj",
+            oauth_token: Some("119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj"),
             oauth_token_secret : "zvNmU9daj9V00118H9KQBozQQsZt4pyLQcZdc",
             oauth_signature_method: SignatureMethod::HMACSHA1,
             oauth_signature: String::new(),
@@ -170,10 +274,13 @@ mod tests {
             oauth_nonce: String::from_str("b9114cda0b95170ff9b164d8226c4b07"),
             realm : None,
             oauth_version : true,
-            callback: test_callback,
+            transmission : Transmission::Header,
+            clock_offset : 0,
+            retry : false,
+            quirks : Default::default(),
         };
         let input = vec![("screen_name", "twitterapi"), ("count", "2")];
-        let base_string = s.get_base_string(HTTPMethod::GET, "https://api.twitter.com/1.1/statuses/user_timeline.json", input);
+        let base_string = s.get_base_string(HTTPMethod::GET, "https://api.twitter.com/1.1/statuses/user_timeline.json", input).unwrap();
         assert_eq!(base_string, expected_base_string);
     }
 
@@ -182,10 +289,10 @@ mod tests {
     /// (https://tools.ietf.org/html/rfc5849#section-3.4.1)
     fn base_string_rfc_test() {
         let expected_base_string = "POST&http%3A%2F%2Fexample.com%2Frequest&a2%3Dr%2520b%26a3%3D2%2520q%26a3%3Da%26b5%3D%253D%25253D%26c%2540%3D%26c2%3D%26oauth_consumer_key%3D9djdj82h48djs9d2%26oauth_nonce%3D7d8f3e4a%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D137131201%26oauth_token%3Dkkk9d7dh3k39sjv7";
-        let s = Session::<bool> {
+        let s = Session {
             oauth_consumer_key: "9djdj82h48djs9d2",
             oauth_consumer_secret: "j49sk3j29djd",
-            oauth_token: "kkk9d7dh3k39sjv7",
+            oauth_token: Some("kkk9d7dh3k39sjv7"),
             oauth_token_secret : "dh893hdasih9",
             oauth_signature_method: SignatureMethod::HMACSHA1,
             oauth_signature: String::new(),
@@ -193,10 +300,13 @@ mod tests {
             oauth_nonce: String::from_str("7d8f3e4a"),
             realm : Some("Example"),
             oauth_version : false,
-            callback: test_callback,
+            transmission : Transmission::Header,
+            clock_offset : 0,
+            retry : false,
+            quirks : Default::default(),
         };
         let input = vec![("c2", ""), ("a3", "2+q")];
-        let base_string = s.get_base_string(HTTPMethod::POST, "http://example.com/request?b5=%3D%253D&a3=a&c%40=&a2=r%20b", input);
+        let base_string = s.get_base_string(HTTPMethod::POST, "http://example.com/request?b5=%3D%253D&a3=a&c%40=&a2=r%20b", input).unwrap();
         assert_eq!(base_string, expected_base_string);
     }
 
@@ -206,27 +316,30 @@ mod tests {
     fn hmac_sha1_signature_test() {
         let expected_signature = String::from_str("tnnArxj06cWHq44gCs1OSKk/jLY=");
         let message = "POST&https%3A%2F%2Fapi.twitter.com%2F1%2Fstatuses%2Fupdate.json&include_entities%3Dtrue%26oauth_consumer_key%3Dxvz1evFS4wEEPTGEFPHBog%26oauth_nonce%3DkYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D1318622958%26oauth_token%3D370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb%26oauth_version%3D1.0%26status%3DHello%2520Ladies%2520%252B%2520Gentlemen%252C%2520a%2520signed%2520OAuth%2520request%2521";
-        let key = format!("{}&{}", encode!("kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw"),
-                                   encode!("LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE"));
-        let signature = SignatureMethod::HMACSHA1.sign(message.to_string(), key);
+        let key = format!("{}&{}", percent_encode("kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw"),
+                                   percent_encode("LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE"));
+        let signature = SignatureMethod::HMACSHA1.sign(message.to_string(), key).unwrap();
         assert_eq!(signature, expected_signature);
     }
 
     #[test]
     /// Verifies that the OAuth header contains all needed values
     fn oauth_header_test() {
-        let s = Session::<bool> {
+        let s = Session {
             oauth_consumer_key: "9djdj82h48djs9d2",
             oauth_consumer_secret: "j49sk3j29djd",
-            oauth_token: "kkk9d7dh3k39sjv7",
+            oauth_token: Some("kkk9d7dh3k39sjv7"),
             oauth_token_secret : "dh893hdasih9",
             oauth_signature_method: SignatureMethod::HMACSHA1,
-            oauth_signature: String::from_str("bYT5CMsGcbgUdFHObYMEfcx6bsw%3D"),
+            oauth_signature: String::from_str("bYT5CMsGcbgUdFHObYMEfcx6bsw="),
             oauth_timestamp: String::from_str("137131201"),
             oauth_nonce: String::from_str("7d8f3e4a"),
             realm : Some("Example"),
             oauth_version : false,
-            callback: test_callback,
+            transmission : Transmission::Header,
+            clock_offset : 0,
+            retry : false,
+            quirks : Default::default(),
         };
         let header = s.get_header();
 
@@ -240,18 +353,44 @@ mod tests {
         assert!(header.contains("oauth_signature=\"bYT5CMsGcbgUdFHObYMEfcx6bsw%3D\""))
     }
 
+    #[test]
+    /// Verifies that every header value is percent encoded, in sorted order
+    fn oauth_header_encoding_test() {
+        let s = Session {
+            oauth_consumer_key: "key+with/reserved=chars",
+            oauth_consumer_secret: "j49sk3j29djd",
+            oauth_token: Some("token~with space"),
+            oauth_token_secret : "dh893hdasih9",
+            oauth_signature_method: SignatureMethod::HMACSHA1,
+            oauth_signature: String::from_str("wOJIO9A2W5mFwDgiDvZbTSMK/PY="),
+            oauth_timestamp: String::from_str("137131201"),
+            oauth_nonce: String::from_str("7d8f3e4a"),
+            realm : Some("Example"),
+            oauth_version : true,
+            transmission : Transmission::Header,
+            clock_offset : 0,
+            retry : false,
+            quirks : Default::default(),
+        };
+        assert_eq!(s.get_header(),
+                   "OAuth realm=\"Example\", oauth_consumer_key=\"key%2Bwith%2Freserved%3Dchars\", \
+                    oauth_nonce=\"7d8f3e4a\", oauth_signature=\"wOJIO9A2W5mFwDgiDvZbTSMK%2FPY%3D\", \
+                    oauth_signature_method=\"HMAC-SHA1\", oauth_timestamp=\"137131201\", \
+                    oauth_token=\"token~with%20space\", oauth_version=\"1.0\"".to_string());
+    }
+
     #[test]
     /// Full OAuth generation tests. Uses the twitter OAuth signature generator
     /// which can be [found here](https://dev.twitter.com/oauth/tools/signature-generator/4128189?nid=731)
     fn oauth_full_flow_twitter_test() {
         let expected_base_string = "GET&https%3A%2F%2Fapi.twitter.com%2F1.1%2Fstatuses%2Fuser_timeline.json&count%3D2%26oauth_consumer_key%3Dk0azC44q2c0DgF7ua9YZ6Q%26oauth_nonce%3Dbfa380dd4f1aadc18145c1385130305b%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D1425427447%26oauth_token%3D119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj%26oauth_version%3D1.0%26screen_name%3Dtwitterapi";
-        let expected_oauth_signature = "BJPEhpBgsJ4WlBDp7v%2BvKp9pTB8%3D";
+        let expected_oauth_signature = "BJPEhpBgsJ4WlBDp7v+vKp9pTB8=";
 
         let input = vec![("screen_name", "twitterapi"), ("count", "2")];
-        let mut s = Session::<bool> {
+        let mut s = Session {
             oauth_consumer_key: "k0azC44q2c0DgF7ua9YZ6Q",
             oauth_consumer_secret: "omqK3feYaKOBgZajh7pqe5AU7oDkmTjLtf1p08ro1M",
-            oauth_token: "119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj",
+            oauth_token: Some("119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj"),
             oauth_token_secret : "zvNmU9daj9V00118H9KQBozQQsZt4pyLQcZdc",
             oauth_signature_method: SignatureMethod::HMACSHA1,
             oauth_signature: String::new(),
@@ -259,12 +398,244 @@ mod tests {
             oauth_nonce: String::from_str("bfa380dd4f1aadc18145c1385130305b"),
             realm : None,
             oauth_version : true,
-            callback: test_callback,
+            transmission : Transmission::Header,
+            clock_offset : 0,
+            retry : false,
+            quirks : Default::default(),
         };
-        let base_string = s.get_base_string(HTTPMethod::GET, "https://api.twitter.com/1.1/statuses/user_timeline.json", input);
+        let base_string = s.get_base_string(HTTPMethod::GET, "https://api.twitter.com/1.1/statuses/user_timeline.json", input).unwrap();
         assert_eq!(base_string, expected_base_string);
 
-        let signature = s.generate_signature(base_string);
+        let signature = s.generate_signature(base_string).unwrap();
         assert_eq!(signature, expected_oauth_signature);
     }
+
+    #[test]
+    /// Verifies that `Transmission::Query` appends the OAuth parameters to the url
+    fn query_transmission_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_transmission(Transmission::Query);
+        let resp = s.request(&url_transport, HTTPMethod::GET, "http://example.com/request?b5=%3D%253D", vec![]).unwrap();
+        let url = resp.get_body_str().unwrap();
+        assert!(url.starts_with("http://example.com/request?b5=%3D%253D&oauth_consumer_key=9djdj82h48djs9d2&"));
+        let signature = s.get_oauth_parameters()[2].1.clone();
+        assert!(url.contains(format!("&oauth_signature={}&", percent_encode(signature.as_slice())).as_slice()));
+        assert!(url.contains("&oauth_token=kkk9d7dh3k39sjv7"));
+        assert!(url.ends_with("&oauth_version=1.0"));
+    }
+
+    #[test]
+    /// Verifies that `Transmission::Body` adds the OAuth parameters to the request data
+    fn body_transmission_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_transmission(Transmission::Body);
+        let resp = s.request(&body_transport, HTTPMethod::POST, "http://example.com/request", vec![("c2", "")]).unwrap();
+        let body = resp.get_body_str().unwrap();
+        assert!(body.starts_with("c2=&oauth_consumer_key=9djdj82h48djs9d2&"));
+        assert!(body.contains("&oauth_signature_method=HMAC-SHA1&"));
+        assert!(body.contains("&oauth_token=kkk9d7dh3k39sjv7&"));
+    }
+
+    #[test]
+    /// Verifies that `Transmission::Body` is refused for a method without a body
+    fn body_transmission_get_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_transmission(Transmission::Body);
+        match s.sign(HTTPMethod::GET, "http://example.com/request", vec![("c2", "")]) {
+            Err(Error::UnsupportedTransmission(Transmission::Body)) => (),
+            _ => panic!("expected Error::UnsupportedTransmission")
+        }
+    }
+
+    #[test]
+    /// Verifies that `Transmission::Header` leaves the OAuth parameters out of the url
+    fn header_transmission_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1);
+        let resp = s.request(&url_transport, HTTPMethod::GET, "http://example.com/request", vec![("a", "b")]).unwrap();
+        assert_eq!(resp.get_body_str(), Some("http://example.com/request?a=b"));
+    }
+
+    #[test]
+    /// Verifies that `sign` returns the request without sending it, using the
+    /// method it was given
+    fn sign_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_realm("Example");
+        let signed = s.sign(HTTPMethod::POST, "http://example.com/request", vec![("a3", "2%20q")]).unwrap();
+        assert_eq!(*signed.get_method(), HTTPMethod::POST);
+        assert_eq!(signed.get_url(), "http://example.com/request");
+        assert_eq!(signed.get_body(), Some("a3=2%20q"));
+        assert!(signed.get_base_string().starts_with("POST&http%3A%2F%2Fexample.com%2Frequest&a3%3D2%2520q%26"));
+        assert_eq!(signed.get_header("Authorization"), Some(s.get_header().as_slice()));
+
+        let expected = s.generate_signature(signed.get_base_string().to_string()).unwrap();
+        assert_eq!(signed.get_signature(), expected.as_slice());
+    }
+
+    #[test]
+    fn consumer_only_test() {
+        let mut s = Session::consumer_only("dpf43f3p2l4k3l03", "kd94hf93k423kf44", SignatureMethod::HMACSHA1);
+        assert!(s.is_consumer_only());
+        let signed = s.sign(HTTPMethod::GET, "http://example.com/lti", vec![("a", "b")]).unwrap();
+        assert!(signed.get_oauth_parameters().iter().all(|&(ref k, _)| k.as_slice() != "oauth_token"));
+        assert!(!signed.get_header("Authorization").unwrap().contains("oauth_token"));
+        assert!(!signed.get_base_string().contains("oauth_token"));
+
+        let key = "kd94hf93k423kf44&".to_string();
+        let expected = SignatureMethod::HMACSHA1.sign(signed.get_base_string().to_string(), key).unwrap();
+        assert_eq!(signed.get_signature(), expected.as_slice());
+    }
+
+    #[test]
+    /// An empty token, such as an unset configuration value, is sent as is rather
+    /// than turning the request into a two-legged one
+    fn empty_token_test() {
+        let mut s = Session::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44", "", "", SignatureMethod::HMACSHA1);
+        assert!(!s.is_consumer_only());
+        let signed = s.sign(HTTPMethod::GET, "http://example.com/lti", vec![]).unwrap();
+        assert!(signed.get_header("Authorization").unwrap().contains("oauth_token=\"\""));
+    }
+
+    #[test]
+    fn consumer_only_plaintext_test() {
+        let mut s = Session::consumer_only("dpf43f3p2l4k3l03", "kd94hf93k423kf44", SignatureMethod::PLAINTEXT)
+                        .set_transmission(Transmission::Query);
+        let signed = s.sign(HTTPMethod::GET, "http://example.com/lti", vec![]).unwrap();
+        assert_eq!(signed.get_signature(), "kd94hf93k423kf44&");
+        assert_eq!(signed.get_url(), "http://example.com/lti?oauth_consumer_key=dpf43f3p2l4k3l03&\
+                                      oauth_signature=kd94hf93k423kf44%26&oauth_signature_method=PLAINTEXT&\
+                                      oauth_version=1.0");
+    }
+
+    #[test]
+    fn quirks_test() {
+        let quirks = Quirks::new().set_nonce_format(NonceFormat::Hex(40))
+                                  .set_timestamp_unit(TimestampUnit::Milliseconds)
+                                  .set_realm_handling(RealmHandling::Omit)
+                                  .set_quoted(false);
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_realm("Example")
+                        .set_quirks(quirks);
+        let signed = s.sign(HTTPMethod::GET, "http://example.com/request", vec![]).unwrap();
+        assert_eq!(s.oauth_nonce.len(), 40);
+        assert!(s.oauth_nonce.chars().all(|c| c.is_digit(16) && !c.is_uppercase()));
+        assert_eq!(s.oauth_timestamp.len(), 13);
+
+        let header = signed.get_header("Authorization").unwrap();
+        assert!(header.starts_with("OAuth oauth_consumer_key=9djdj82h48djs9d2, "));
+        assert!(!header.contains("realm"));
+        assert_eq!(header, s.get_header().as_slice());
+    }
+
+    #[test]
+    fn sign_method_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1);
+        let methods = vec![(HTTPMethod::GET, "GET&"), (HTTPMethod::POST, "POST&"),
+                           (HTTPMethod::DELETE, "DELETE&"), (HTTPMethod::PUT, "PUT&"),
+                           (HTTPMethod::HEAD, "HEAD&"), (HTTPMethod::PATCH, "PATCH&"),
+                           (HTTPMethod::OPTIONS, "OPTIONS&"), (HTTPMethod::TRACE, "TRACE&"),
+                           (HTTPMethod::Extension(String::from_str("propfind")), "PROPFIND&")];
+        for (method, prefix) in methods.into_iter() {
+            let signed = s.sign(method.clone(), "http://example.com/request", vec![("a", "b")]).unwrap();
+            assert_eq!(*signed.get_method(), method);
+            assert!(signed.get_base_string().starts_with(prefix));
+            let expected = s.generate_signature(signed.get_base_string().to_string()).unwrap();
+            assert_eq!(signed.get_signature(), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn request_method_test() {
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1);
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(*req.get_method(), HTTPMethod::PATCH);
+            assert!(req.get_base_string().starts_with("PATCH&"));
+            assert_eq!(req.get_body(), Some("a=b"));
+            Ok(Response::new(200, vec![], vec![]))
+        };
+        s.request(&transport, HTTPMethod::PATCH, "http://example.com/request", vec![("a", "b")]).unwrap();
+    }
+
+    #[test]
+    fn learn_clock_offset_from_date_test() {
+        use oauth1::client::time::get_time;
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1);
+        let resp = Response::new(200, vec![("Date".to_string(), "Sun, 06 Nov 1994 08:49:37 GMT".to_string())], vec![]);
+        assert!(s.learn_clock_offset(&resp));
+        assert!((s.get_clock_offset() + get_time().sec - 784111777).abs() <= 1);
+        assert!(!s.learn_clock_offset(&Response::new(200, vec![], vec![])));
+    }
+
+    #[test]
+    fn timestamp_refused_retry_test() {
+        use std::cell::Cell;
+        use oauth1::client::time::get_time;
+        let server_time = get_time().sec + 600;
+        let calls = Cell::new(0);
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            calls.set(calls.get() + 1);
+            let timestamp : i64 = req.get_timestamp().unwrap().parse().unwrap();
+            if (timestamp - server_time).abs() > 5 {
+                let header = format!("OAuth oauth_problem=\"timestamp_refused\", \
+                                      oauth_acceptable_timestamps=\"{}-{}\"", server_time - 300, server_time + 300);
+                Ok(Response::new(401, vec![("WWW-Authenticate".to_string(), header)], vec![]))
+            } else {
+                Ok(Response::new(200, vec![], vec![]))
+            }
+        };
+
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1);
+        assert_eq!(s.request(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_status(), 401);
+        assert_eq!(calls.get(), 1);
+        // the offset was learned, so the next request is accepted
+        assert_eq!(s.request(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_status(), 200);
+
+        calls.set(0);
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_retry(true);
+        assert_eq!(s.request(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_status(), 200);
+        assert_eq!(calls.get(), 2);
+
+        // without the provider's time, the request is still retried with a fresh timestamp
+        calls.set(0);
+        let refused = |_: &SignedRequest| -> Result<Response, Error> {
+            calls.set(calls.get() + 1);
+            Ok(Response::new(401, vec![], b"oauth_problem=timestamp_refused".to_vec()))
+        };
+        assert_eq!(s.request(&refused, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_status(), 401);
+        assert_eq!(calls.get(), 2);
+        assert!((s.get_clock_offset() - 600).abs() <= 5);
+    }
+
+    #[test]
+    fn nonce_used_retry_test() {
+        use std::cell::RefCell;
+        let nonces = RefCell::new(Vec::new());
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            nonces.borrow_mut().push(req.get_nonce().unwrap().to_string());
+            Ok(Response::new(401, vec![], b"oauth_problem=nonce_used".to_vec()))
+        };
+        let mut s = Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7",
+                                 "dh893hdasih9", SignatureMethod::HMACSHA1)
+                        .set_retry(true);
+        match s.request(&transport, HTTPMethod::GET, "http://example.com/", vec![]).unwrap().get_problem() {
+            Some(ref p) => assert_eq!(*p.get_kind(), ProblemKind::NonceUsed),
+            None => panic!("expected a problem")
+        }
+        // retried once only, with a fresh nonce
+        let nonces = nonces.borrow();
+        assert_eq!(nonces.len(), 2);
+        assert!(nonces[0] != nonces[1]);
+    }
 }
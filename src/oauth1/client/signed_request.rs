@@ -0,0 +1,229 @@
+//! A fully signed request, independent of any http library. It holds everything
+//! needed to send the request later, or from another thread: the final url, the
+//! method, the form-encoded body and the headers, along with the signature details.
+
+use super::{HTTPMethod, Transmission, concat, has_body, percent_encode};
+use super::quirks::Quirks;
+use super::response::FORM_URLENCODED;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedRequest {
+    method : HTTPMethod,
+    url : String,
+    body : Option<String>,
+    headers : Vec<(String, String)>,
+    oauth_parameters : Vec<(String, String)>,
+    base_string : String,
+}
+
+impl SignedRequest {
+    /// Assembles a signed request. `data` must already be percent encoded, as it is
+    /// for the base string, and `oauth_parameters` are the unencoded protocol parameters,
+    /// including `oauth_signature`.
+    ///
+    /// `data` is sent in the body for methods that carry one, and in the query otherwise.
+    /// The OAuth parameters are placed according to `transmission`.
+    pub fn new(method: HTTPMethod, base_url: &str, data: Vec<(&str, &str)>,
+               transmission: Transmission, realm: Option<&str>,
+               oauth_parameters: Vec<(String, String)>, base_string: String) -> SignedRequest {
+        SignedRequest::with_quirks(method, base_url, data, transmission, realm, oauth_parameters,
+                                   base_string, &Quirks::new())
+    }
+
+    /// Like `new`, but serializes the `Authorization` header following `quirks`
+    pub fn with_quirks(method: HTTPMethod, base_url: &str, data: Vec<(&str, &str)>,
+                       transmission: Transmission, realm: Option<&str>,
+                       oauth_parameters: Vec<(String, String)>, base_string: String,
+                       quirks: &Quirks) -> SignedRequest {
+        let to_pair = | (key, value) : (&str, &str) | -> String { format!("{}={}", key, value) };
+        let encoded : Vec<String> = oauth_parameters.iter()
+                                                    .map(|&(ref k, ref v)| format!("{}={}",
+                                                         percent_encode(k.as_slice()),
+                                                         percent_encode(v.as_slice())))
+                                                    .collect();
+        let data : Vec<String> = data.into_iter().map(to_pair).collect();
+
+        let mut query = Vec::new();
+        let mut body = Vec::new();
+        let mut headers = Vec::new();
+        if has_body(&method) {
+            body.push_all(data.as_slice());
+        } else {
+            query.push_all(data.as_slice());
+        }
+        match transmission {
+            Transmission::Header => headers.push((String::from_str("Authorization"),
+                                                  quirks.header(realm, oauth_parameters.as_slice()))),
+            Transmission::Body => body.push_all(encoded.as_slice()),
+            Transmission::Query => query.push_all(encoded.as_slice()),
+        };
+
+        let url = match query.len() {
+            0 => base_url.to_string(),
+            _ => {
+                let sep = if base_url.contains_char('?') { "&" } else { "?" };
+                format!("{}{}{}", base_url, sep, concat(query.as_slice(), "&"))
+            }
+        };
+        let body = match body.len() {
+            0 => None,
+            _ => {
+                headers.push((String::from_str("Content-Type"), String::from_str(FORM_URLENCODED)));
+                Some(concat(body.as_slice(), "&"))
+            }
+        };
+
+        SignedRequest {
+            method : method,
+            url : url,
+            body : body,
+            headers : headers,
+            oauth_parameters : oauth_parameters,
+            base_string : base_string,
+        }
+    }
+
+    /// Wraps a request signed elsewhere, such as a delegated OAuth Echo request, so it
+    /// can be sent with a `Transport`. `authorization` is sent unchanged as the
+    /// `Authorization` header; the OAuth parameters and base string are not known
+    pub fn presigned(method: HTTPMethod, url: &str, authorization: &str) -> SignedRequest {
+        SignedRequest {
+            method : method,
+            url : url.to_string(),
+            body : None,
+            headers : vec![(String::from_str("Authorization"), authorization.to_string())],
+            oauth_parameters : Vec::new(),
+            base_string : String::new(),
+        }
+    }
+
+    pub fn get_method(&self) -> &HTTPMethod {
+        &self.method
+    }
+
+    /// Returns the url to send the request to, including its query
+    pub fn get_url(&self) -> &str {
+        self.url.as_slice()
+    }
+
+    /// Returns the form-encoded body, if the request has one
+    pub fn get_body(&self) -> Option<&str> {
+        self.body.as_ref().map(|b| b.as_slice())
+    }
+
+    /// Returns every header to send, as `(name, value)` pairs
+    pub fn get_headers(&self) -> &[(String, String)] {
+        self.headers.as_slice()
+    }
+
+    /// Returns the value of the header `name`, compared case-insensitively
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        use std::ascii::AsciiExt;
+        self.headers.iter()
+                    .find(|&&(ref k, _)| k.as_slice().eq_ignore_ascii_case(name))
+                    .map(|&(_, ref v)| v.as_slice())
+    }
+
+    /// Returns the unencoded OAuth protocol parameters, including `oauth_signature`
+    pub fn get_oauth_parameters(&self) -> &[(String, String)] {
+        self.oauth_parameters.as_slice()
+    }
+
+    /// Returns the base string that was signed
+    pub fn get_base_string(&self) -> &str {
+        self.base_string.as_slice()
+    }
+
+    /// Returns the unencoded `oauth_signature`
+    pub fn get_signature(&self) -> &str {
+        self.get_oauth_parameter("oauth_signature").unwrap_or("")
+    }
+
+    /// Returns `oauth_nonce`, absent for `PLAINTEXT` signatures
+    pub fn get_nonce(&self) -> Option<&str> {
+        self.get_oauth_parameter("oauth_nonce")
+    }
+
+    /// Returns `oauth_timestamp`, absent for `PLAINTEXT` signatures
+    pub fn get_timestamp(&self) -> Option<&str> {
+        self.get_oauth_parameter("oauth_timestamp")
+    }
+
+    fn get_oauth_parameter(&self, name: &str) -> Option<&str> {
+        self.oauth_parameters.iter()
+                             .find(|&&(ref k, _)| k.as_slice() == name)
+                             .map(|&(_, ref v)| v.as_slice())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::SignedRequest;
+    use oauth1::client::{HTTPMethod, Transmission};
+
+    fn oauth_parameters() -> Vec<(String, String)> {
+        vec![("oauth_consumer_key".to_string(), "key".to_string()),
+             ("oauth_nonce".to_string(), "abc".to_string()),
+             ("oauth_signature".to_string(), "si/g=".to_string()),
+             ("oauth_timestamp".to_string(), "137131201".to_string())]
+    }
+
+    #[test]
+    fn signed_request_header_get() {
+        let req = SignedRequest::new(HTTPMethod::GET, "http://example.com/a?b=c", vec![("d", "e%20f")],
+                                     Transmission::Header, Some("Example"), oauth_parameters(),
+                                     String::from_str("GET&..."));
+        assert_eq!(req.get_url(), "http://example.com/a?b=c&d=e%20f");
+        assert_eq!(req.get_body(), None);
+        assert_eq!(req.get_header("authorization"),
+                   Some("OAuth realm=\"Example\", oauth_consumer_key=\"key\", oauth_nonce=\"abc\", \
+                         oauth_signature=\"si%2Fg%3D\", oauth_timestamp=\"137131201\""));
+        assert_eq!(req.get_header("Content-Type"), None);
+        assert_eq!(req.get_signature(), "si/g=");
+        assert_eq!(req.get_nonce(), Some("abc"));
+        assert_eq!(req.get_timestamp(), Some("137131201"));
+    }
+
+    #[test]
+    fn signed_request_body_post() {
+        let req = SignedRequest::new(HTTPMethod::POST, "http://example.com/a", vec![("d", "e")],
+                                     Transmission::Body, Some("Example"), oauth_parameters(),
+                                     String::from_str("POST&..."));
+        assert_eq!(req.get_url(), "http://example.com/a");
+        assert_eq!(req.get_body(), Some("d=e&oauth_consumer_key=key&oauth_nonce=abc&\
+                                         oauth_signature=si%2Fg%3D&oauth_timestamp=137131201"));
+        assert_eq!(req.get_header("Authorization"), None);
+        assert_eq!(req.get_header("Content-Type"), Some("application/x-www-form-urlencoded"));
+    }
+
+    #[test]
+    fn signed_request_query_post() {
+        let req = SignedRequest::new(HTTPMethod::POST, "http://example.com/a", vec![("d", "e")],
+                                     Transmission::Query, None, oauth_parameters(),
+                                     String::from_str("POST&..."));
+        assert_eq!(req.get_url(), "http://example.com/a?oauth_consumer_key=key&oauth_nonce=abc&\
+                                   oauth_signature=si%2Fg%3D&oauth_timestamp=137131201");
+        assert_eq!(req.get_body(), Some("d=e"));
+        assert_eq!(req.get_header("Authorization"), None);
+    }
+
+    #[test]
+    fn signed_request_method_data() {
+        let body_methods = vec![HTTPMethod::POST, HTTPMethod::PUT, HTTPMethod::PATCH];
+        let query_methods = vec![HTTPMethod::GET, HTTPMethod::HEAD, HTTPMethod::DELETE, HTTPMethod::OPTIONS,
+                                 HTTPMethod::TRACE, HTTPMethod::Extension(String::from_str("PROPFIND"))];
+        for method in body_methods.into_iter() {
+            let req = SignedRequest::new(method, "http://example.com/a", vec![("d", "e")],
+                                         Transmission::Header, None, oauth_parameters(), String::new());
+            assert_eq!(req.get_url(), "http://example.com/a");
+            assert_eq!(req.get_body(), Some("d=e"));
+        }
+        for method in query_methods.into_iter() {
+            let req = SignedRequest::new(method, "http://example.com/a", vec![("d", "e")],
+                                         Transmission::Header, None, oauth_parameters(), String::new());
+            assert_eq!(req.get_url(), "http://example.com/a?d=e");
+            assert_eq!(req.get_body(), None);
+        }
+    }
+}
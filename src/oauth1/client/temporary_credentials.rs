@@ -1,42 +1,108 @@
-use super::{AuthorizationHeader, generate_nonce, generate_timestamp};
+//! Request for temporary credentials, the first step of the three-legged flow, as
+//! described in [RFC 5849 &sect; 2.1](http://tools.ietf.org/html/rfc5849#section-2.1)
+
+use super::{HTTPMethod, AuthorizationHeader, BaseString, Transmission, protocol_parameters,
+            base_string_parameters, signing_key, generate_nonce, generate_timestamp, percent_encode,
+            retry_refused};
+use super::response::{CredentialsResponse, ResponseError};
+use super::quirks::Quirks;
+use super::signed_request::SignedRequest;
+use super::transport::{Response, Transport};
+use super::async_transport::{self, AsyncTransport, Pending};
 use ::crypto::SignatureMethod;
+use ::error::Error;
 use std::default::Default;
 
 #[derive(Clone)]
 pub struct Builder<'a> {
     request_url         : &'a str,
     consumer_key        : &'a str,
-    callback_url        : &'a str,
+    consumer_secret     : &'a str,
+    callback_url        : Option<&'a str>,
     signature_method    : SignatureMethod,
+    quirks              : Quirks,
     version             : Option<&'a str>,
-    realm               : Option<&'a str>
+    realm               : Option<&'a str>,
+    parameters          : Vec<(&'a str, &'a str)>,
+    clock_offset        : i64,
+    retry               : bool,
+    legacy              : bool
 }
 
 #[derive(Clone)]
 pub struct TemporaryCredentials<'a> {
     request_url         : &'a str,
     consumer_key        : &'a str,
-    callback_url        : &'a str,
+    consumer_secret     : &'a str,
+    callback_url        : Option<&'a str>,
     signature_method    : SignatureMethod,
+    quirks              : Quirks,
+    version             : Option<&'a str>,
     realm               : Option<&'a str>,
+    parameters          : Vec<(&'a str, &'a str)>,
+    clock_offset        : i64,
+    retry               : bool,
+    legacy              : bool,
     timestamp           : String,
     nonce               : String,
     signature           : String,
 }
 
 impl<'a> Builder<'a> {
-    pub fn new(request_url : &'a str, consumer_key : &'a str, callback_url : &'a str,
-               signature_method : SignatureMethod) -> Builder<'a> {
+    /// `callback_url` may be `oob` when the client cannot receive callbacks
+    pub fn new(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+               callback_url : &'a str, signature_method : SignatureMethod) -> Builder<'a> {
+        let mut builder = Builder::without_callback(request_url, consumer_key, consumer_secret,
+                                                    signature_method);
+        builder.callback_url = Some(callback_url);
+        builder
+    }
+
+    /// Builds a request sending no `oauth_callback`, which OAuth 1.0a requires, so this
+    /// is only used by `reverse_auth` and `legacy`
+    fn without_callback(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                        signature_method : SignatureMethod) -> Builder<'a> {
         Builder {
             request_url         : request_url,
             consumer_key        : consumer_key,
-            callback_url        : callback_url,
+            consumer_secret     : consumer_secret,
+            callback_url        : None,
             signature_method    : signature_method,
+            quirks              : Default::default(),
             version             : None,
-            realm               : None
+            realm               : None,
+            parameters          : Vec::new(),
+            clock_offset        : 0,
+            retry               : false,
+            legacy              : false
         }
     }
 
+    /// Builds the first half of reverse auth: a request made with `x_auth_mode=reverse_auth`
+    /// and no callback, whose signed response is handed to a device to exchange for
+    /// token credentials with `token_credentials::Builder::reverse_auth`
+    pub fn reverse_auth(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                        signature_method : SignatureMethod) -> Builder<'a> {
+        Builder::without_callback(request_url, consumer_key, consumer_secret, signature_method)
+            .add_parameter("x_auth_mode", "reverse_auth")
+    }
+
+    /// Builds a request for temporary credentials to a provider speaking OAuth Core 1.0,
+    /// which predates 1.0a: no callback is sent, as it is given at the authorization
+    /// step instead (see `providers::Provider::legacy_authorize_url`), and the response
+    /// is not expected to confirm it. Such providers issue no verifier either, so the
+    /// token credentials are requested with `token_credentials::Builder::legacy`.
+    ///
+    /// This flow is open to session fixation: an attacker can start it, and have a
+    /// victim authorize the temporary credentials, after which the attacker obtains
+    /// the token credentials. Only use it with providers which cannot be upgraded
+    pub fn legacy(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                  signature_method : SignatureMethod) -> Builder<'a> {
+        let mut builder = Builder::without_callback(request_url, consumer_key, consumer_secret, signature_method);
+        builder.legacy = true;
+        builder
+    }
+
     pub fn use_version(mut self)-> Builder<'a> {
         self.version = Some("1.0");
         self
@@ -47,13 +113,45 @@ impl<'a> Builder<'a> {
         self
     }
 
+    pub fn set_quirks(mut self, quirks : Quirks) -> Builder<'a> {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Adds `offset` seconds to the timestamp, see `Session::set_clock_offset`
+    pub fn set_clock_offset(mut self, offset : i64) -> Builder<'a> {
+        self.clock_offset = offset;
+        self
+    }
+
+    /// Signs and sends the request once more when the provider refuses it, see
+    /// `Session::set_retry`
+    pub fn set_retry(mut self, retry : bool) -> Builder<'a> {
+        self.retry = retry;
+        self
+    }
+
+    /// Adds a provider specific parameter, such as `scope` or `x_auth_access_type`,
+    /// which is sent in the body and signed. `value` must not be percent encoded
+    pub fn add_parameter(mut self, key : &'a str, value : &'a str) -> Builder<'a> {
+        self.parameters.push((key, value));
+        self
+    }
+
     pub fn create(self) -> TemporaryCredentials<'a> {
         TemporaryCredentials {
             request_url         : self.request_url,
             consumer_key        : self.consumer_key,
+            consumer_secret     : self.consumer_secret,
             callback_url        : self.callback_url,
             signature_method    : self.signature_method,
+            quirks              : self.quirks,
+            version             : self.version,
             realm               : self.realm,
+            parameters          : self.parameters,
+            clock_offset        : self.clock_offset,
+            retry               : self.retry,
+            legacy              : self.legacy,
             timestamp           : Default::default(),
             nonce               : Default::default(),
             signature           : Default::default(),
@@ -62,44 +160,298 @@ impl<'a> Builder<'a> {
 }
 
 impl<'a> TemporaryCredentials<'a> {
-    pub fn request(&mut self) -> Result<(),()> {
-        self.timestamp = generate_timestamp();
-        self.nonce = generate_nonce();
-        Ok(())
+    /// Signs the request for temporary credentials without sending it
+    pub fn sign(&mut self) -> Result<SignedRequest, Error> {
+        let encoded : Vec<(String, String)> = self.parameters.iter()
+                                                  .map(|&(k, v)| (percent_encode(k), percent_encode(v)))
+                                                  .collect();
+        let data : Vec<(&str, &str)> = encoded.iter()
+                                              .map(|&(ref k, ref v)| (k.as_slice(), v.as_slice()))
+                                              .collect();
+
+        if self.legacy {
+            warn!("requesting temporary credentials from {} with OAuth 1.0, which is open to \
+                   session fixation; use OAuth 1.0a where the provider supports it", self.request_url);
+        }
+        self.timestamp = generate_timestamp(self.clock_offset, self.quirks.get_timestamp_unit());
+        self.nonce = try!(generate_nonce(self.quirks.get_nonce_format()));
+        let base_string = try!(self.get_base_string(HTTPMethod::POST, self.request_url, data.clone()));
+        self.signature = try!(self.signature_method.sign(base_string.clone(),
+                                                         signing_key(self.consumer_secret, "")));
+        Ok(SignedRequest::with_quirks(HTTPMethod::POST, self.request_url, data, Transmission::Header,
+                                      self.realm, self.get_oauth_parameters(), base_string, &self.quirks))
+    }
+
+    /// Obtains temporary credentials, sending the request with `transport`. Fails
+    /// unless the provider confirms the callback with `oauth_callback_confirmed=true`,
+    /// except for requests built with `Builder::legacy`
+    pub fn request<T: Transport>(&mut self, transport: &T) -> Result<CredentialsResponse, Error> {
+        let response = try!(self.send(transport));
+        if self.legacy {
+            parse_legacy_temporary_credentials(response)
+        } else {
+            parse_temporary_credentials(response)
+        }
+    }
+
+    /// Like `request`, but sends the request with an `AsyncTransport` and returns
+    /// without waiting for the response
+    pub fn request_async<T: AsyncTransport>(&mut self, transport: &T)
+                                            -> Result<Pending<CredentialsResponse>, Error> {
+        let signed = try!(self.sign());
+        let parse : fn(Response) -> Result<CredentialsResponse, Error> = if self.legacy {
+            parse_legacy_temporary_credentials
+        } else {
+            parse_temporary_credentials
+        };
+        Ok(async_transport::send(transport, signed, parse))
+    }
+
+    /// Obtains the parameters of a reverse auth request, built with
+    /// `Builder::reverse_auth`, sending it with `transport`. They are returned unparsed,
+    /// as the device must send them back unchanged as `x_reverse_auth_parameters`
+    pub fn request_reverse_auth<T: Transport>(&mut self, transport: &T) -> Result<String, Error> {
+        parse_reverse_auth_parameters(try!(self.send(transport)))
+    }
+
+    /// Like `request_reverse_auth`, but sends the request with an `AsyncTransport` and
+    /// returns without waiting for the response
+    pub fn request_reverse_auth_async<T: AsyncTransport>(&mut self, transport: &T)
+                                                         -> Result<Pending<String>, Error> {
+        let signed = try!(self.sign());
+        Ok(async_transport::send(transport, signed, parse_reverse_auth_parameters))
+    }
+
+    /// Returns the clock offset, which is updated when the provider refuses the timestamp
+    pub fn get_clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+
+    /// Signs the request and sends it with `transport`, once more if the provider
+    /// refuses it and `Builder::set_retry` was used
+    fn send<T: Transport>(&mut self, transport: &T) -> Result<Response, Error> {
+        let signed = try!(self.sign());
+        let response = try!(transport.send(&signed));
+        if !retry_refused(&response, &mut self.clock_offset, self.retry) {
+            return Ok(response);
+        }
+        let signed = try!(self.sign());
+        transport.send(&signed)
+    }
+}
+
+/// Parses the response of the temporary credentials endpoint, ensuring the provider
+/// confirmed the callback, as required by OAuth 1.0a
+fn parse_temporary_credentials(response: Response) -> Result<CredentialsResponse, Error> {
+    try!(response.check_problem());
+    let credentials = try!(CredentialsResponse::from_response(&response));
+    match credentials.get_callback_confirmed() {
+        Some(true) => Ok(credentials),
+        _ => Err(Error::Provider(ResponseError::CallbackNotConfirmed))
+    }
+}
+
+/// Parses the response of an OAuth 1.0 temporary credentials endpoint, which does not
+/// confirm the callback
+fn parse_legacy_temporary_credentials(response: Response) -> Result<CredentialsResponse, Error> {
+    try!(response.check_problem());
+    Ok(try!(CredentialsResponse::from_response(&response)))
+}
+
+/// Returns the body of a reverse auth response, the `OAuth ...` header the provider
+/// signed for the device
+fn parse_reverse_auth_parameters(response: Response) -> Result<String, Error> {
+    try!(response.check_problem());
+    if !response.is_success() {
+        return Err(Error::Provider(ResponseError::UnexpectedStatus(response.get_status())));
+    }
+    match response.get_body_str() {
+        Some(body) => Ok(body.trim().to_string()),
+        None => Err(Error::Provider(ResponseError::InvalidEncoding))
     }
 }
 
 impl<'a> AuthorizationHeader for TemporaryCredentials<'a> {
-    fn get_header(&self) -> String {
-        let header = format!("Authorization: OAuth {}oauth_consumer_key=\"{}\", \
-                oauth_signature=\"{}\", oauth_signature_method=\"{}\", oauth_version=\"1.0\"",
-                match self.realm {
-                    None => Default::default(),
-                    Some(r) => format!("Realm=\"{}\"", r)
-                },
-                self.consumer_key, self.signature, self.signature_method);
-
-        match self.signature_method {
-            SignatureMethod::PLAINTEXT => header,
-            _ => format!("{}, oauth_timestamp=\"{}\", oauth_nonce=\"{}\"",
-                         header, self.timestamp, self.nonce)
-        }
+    fn get_oauth_parameters(&self) -> Vec<(String, String)> {
+        let others = match self.callback_url {
+            Some(callback) => vec![("oauth_callback", callback)],
+            None => vec![]
+        };
+        protocol_parameters(self.consumer_key, self.signature_method, self.nonce.as_slice(),
+                            self.timestamp.as_slice(), self.signature.as_slice(), self.version, others)
+    }
+
+    fn get_realm(&self) -> Option<&str> {
+        self.realm
+    }
+
+    fn get_quirks(&self) -> Quirks {
+        self.quirks.clone()
     }
 }
 
 
 impl <'a> super::BaseString for TemporaryCredentials<'a> {
     fn get_self_paramaters(&self) ->  Vec<String>{
-        let mut params = Vec::new();
-        match self.signature_method {
-            SignatureMethod::PLAINTEXT  => (),
-            _                           => {
-                params.push(format!("oauth_timestamp={}", self.timestamp));
-                params.push(format!("oauth_nonce={}", self.nonce));
-            }
+        base_string_parameters(self.get_oauth_parameters())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Builder;
+    use oauth1::client::{AuthorizationHeader, BaseString, HTTPMethod};
+    use oauth1::client::response::ResponseError;
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::transport::Response;
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    #[test]
+    /// Verifies the header sent to the temporary credentials endpoint, using the
+    /// example from [RFC 5849 &sect; 2.1](http://tools.ietf.org/html/rfc5849#section-2.1)
+    fn temporary_credentials_header_test() {
+        let mut creds = Builder::new("https://photos.example.net/initiate", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "http://printer.example.com/ready",
+                                     SignatureMethod::HMACSHA1)
+                            .set_realm("Photos")
+                            .create();
+        creds.timestamp = String::from_str("137131200");
+        creds.nonce = String::from_str("wIjqoS");
+        creds.signature = String::from_str("74KNZJeDHnMBp0EMJ9ZHt/XKycU=");
+
+        assert_eq!(creds.get_header(),
+                   "OAuth realm=\"Photos\", oauth_callback=\"http%3A%2F%2Fprinter.example.com%2Fready\", \
+                    oauth_consumer_key=\"dpf43f3p2l4k3l03\", oauth_nonce=\"wIjqoS\", \
+                    oauth_signature=\"74KNZJeDHnMBp0EMJ9ZHt%2FXKycU%3D\", \
+                    oauth_signature_method=\"HMAC-SHA1\", oauth_timestamp=\"137131200\"".to_string());
+    }
+
+    #[test]
+    /// Base string from [RFC 5849 &sect; 2.1](http://tools.ietf.org/html/rfc5849#section-2.1)
+    fn temporary_credentials_base_string_test() {
+        let expected_base_string = "POST&https%3A%2F%2Fphotos.example.net%2Finitiate&oauth_callback%3Dhttp%253A%252F%252Fprinter.example.com%252Fready%26oauth_consumer_key%3Ddpf43f3p2l4k3l03%26oauth_nonce%3DwIjqoS%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D137131200";
+        let mut creds = Builder::new("https://photos.example.net/initiate", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "http://printer.example.com/ready",
+                                     SignatureMethod::HMACSHA1)
+                            .create();
+        creds.timestamp = String::from_str("137131200");
+        creds.nonce = String::from_str("wIjqoS");
+
+        let base_string = creds.get_base_string(HTTPMethod::POST, "https://photos.example.net/initiate", vec![]).unwrap();
+        assert_eq!(base_string, expected_base_string);
+    }
+
+    #[test]
+    /// Signature from [RFC 5849 &sect; 2.1](http://tools.ietf.org/html/rfc5849#section-2.1)
+    fn temporary_credentials_exchange_test() {
+        let mut creds = Builder::new("https://photos.example.net/initiate", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "http://printer.example.com/ready",
+                                     SignatureMethod::HMACSHA1)
+                            .set_realm("Photos")
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(*req.get_method(), HTTPMethod::POST);
+            assert_eq!(req.get_url(), "https://photos.example.net/initiate");
+            assert!(req.get_header("Authorization").unwrap().contains("oauth_callback="));
+            let body = b"oauth_token=hh5s93j4hdidpola&oauth_token_secret=hdhd0244k9j7ao03&oauth_callback_confirmed=true";
+            Ok(Response::new(200, vec![], body.to_vec()))
+        };
+        let resp = creds.request(&transport).unwrap();
+        assert_eq!(resp.get_token(), "hh5s93j4hdidpola");
+        assert_eq!(resp.get_token_secret(), "hdhd0244k9j7ao03");
+
+        creds.timestamp = String::from_str("137131200");
+        creds.nonce = String::from_str("wIjqoS");
+        let base_string = creds.get_base_string(HTTPMethod::POST, "https://photos.example.net/initiate", vec![]).unwrap();
+        let signature = SignatureMethod::HMACSHA1.sign(base_string, String::from_str("kd94hf93k423kf44&")).unwrap();
+        assert_eq!(signature, String::from_str("74KNZJeDHnMBp0EMJ9ZHt/XKycU="));
+    }
+
+    #[test]
+    fn temporary_credentials_empty_callback() {
+        // an empty callback is sent as is, rather than mistaken for a request without one
+        let creds = Builder::new("https://photos.example.net/initiate", "dpf43f3p2l4k3l03",
+                                 "kd94hf93k423kf44", "", SignatureMethod::HMACSHA1)
+                        .create();
+        assert!(creds.get_header().contains("oauth_callback=\"\""));
+    }
+
+    #[test]
+    fn temporary_credentials_unconfirmed_callback() {
+        let mut creds = Builder::new("https://photos.example.net/initiate", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "oob", SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |_: &SignedRequest| -> Result<Response, Error> {
+            Ok(Response::new(200, vec![], b"oauth_token=a&oauth_token_secret=b".to_vec()))
+        };
+        match creds.request(&transport) {
+            Err(Error::Provider(ResponseError::CallbackNotConfirmed)) => (),
+            _ => panic!("expected ResponseError::CallbackNotConfirmed")
+        }
+    }
+
+    #[test]
+    fn temporary_credentials_extra_parameters() {
+        let mut creds = Builder::new("https://www.google.com/accounts/OAuthGetRequestToken", "anonymous",
+                                     "anonymous", "oob", SignatureMethod::HMACSHA1)
+                            .add_parameter("scope", "https://www.google.com/m8/feeds/")
+                            .add_parameter("x_auth_access_type", "read")
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(req.get_body(), Some("scope=https%3A%2F%2Fwww.google.com%2Fm8%2Ffeeds%2F&\
+                                             x_auth_access_type=read"));
+            assert_eq!(req.get_header("Content-Type"), Some("application/x-www-form-urlencoded"));
+            assert!(req.get_base_string().ends_with("%26scope%3Dhttps%253A%252F%252Fwww.google.com%252Fm8%252Ffeeds%252F\
+                                                     %26x_auth_access_type%3Dread"));
+            let expected = SignatureMethod::HMACSHA1.sign(req.get_base_string().to_string(),
+                                                          String::from_str("anonymous&")).unwrap();
+            assert_eq!(req.get_signature(), expected.as_slice());
+            let body = b"oauth_token=a&oauth_token_secret=b&oauth_callback_confirmed=true";
+            Ok(Response::new(200, vec![], body.to_vec()))
+        };
+        assert_eq!(creds.request(&transport).unwrap().get_token(), "a");
+    }
+
+    #[test]
+    fn temporary_credentials_legacy() {
+        let mut creds = Builder::legacy("https://legacy.example.com/request_token", "dpf43f3p2l4k3l03",
+                                        "kd94hf93k423kf44", SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert!(!req.get_header("Authorization").unwrap().contains("oauth_callback"));
+            assert!(!req.get_base_string().contains("oauth_callback"));
+            Ok(Response::new(200, vec![], b"oauth_token=a&oauth_token_secret=b".to_vec()))
+        };
+        let resp = creds.request(&transport).unwrap();
+        assert_eq!(resp.get_token(), "a");
+        assert_eq!(resp.get_callback_confirmed(), None);
+    }
+
+    #[test]
+    fn temporary_credentials_reverse_auth() {
+        let mut creds = Builder::reverse_auth("https://api.twitter.com/oauth/request_token", "server_key",
+                                              "server_secret", SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(req.get_body(), Some("x_auth_mode=reverse_auth"));
+            assert!(!req.get_header("Authorization").unwrap().contains("oauth_callback"));
+            assert!(req.get_base_string().ends_with("%26x_auth_mode%3Dreverse_auth"));
+            let body = b"OAuth oauth_nonce=\"abc\", oauth_signature_method=\"HMAC-SHA1\", \
+                         oauth_consumer_key=\"server_key\", oauth_signature=\"xyz%3D\"\n";
+            Ok(Response::new(200, vec![("Content-Type".to_string(), "text/plain".to_string())], body.to_vec()))
         };
-        params.push(format!("oauth_consumer_key={}", self.consumer_key));
-        params.push(format!("oauth_signature_method={}", self.signature_method));
-        params
+        let parameters = creds.request_reverse_auth(&transport).unwrap();
+        assert_eq!(parameters, "OAuth oauth_nonce=\"abc\", oauth_signature_method=\"HMAC-SHA1\", \
+                                oauth_consumer_key=\"server_key\", oauth_signature=\"xyz%3D\"".to_string());
+
+        let refused = |_: &SignedRequest| -> Result<Response, Error> {
+            Ok(Response::new(403, vec![], b"Reverse auth is not enabled".to_vec()))
+        };
+        match creds.request_reverse_auth(&refused) {
+            Err(Error::Provider(ResponseError::UnexpectedStatus(403))) => (),
+            _ => panic!("expected ResponseError::UnexpectedStatus")
+        }
     }
 }
@@ -0,0 +1,474 @@
+//! Request for token credentials, made with the temporary credentials and the
+//! verifier obtained once the resource owner authorized the client, as described
+//! in [RFC 5849 &sect; 2.3](http://tools.ietf.org/html/rfc5849#section-2.3).
+//!
+//! It also renews expiring token credentials, with the session handle issued along
+//! with them, as described in the [OAuth Session Extension]
+//! (http://oauth.googlecode.com/svn/spec/ext/session/1.0/drafts/1/spec.html)
+//!
+//! Devices holding token credentials under their own consumer complete reverse auth
+//! with it, obtaining token credentials for a server's consumer
+
+use super::{HTTPMethod, AuthorizationHeader, BaseString, Transmission, protocol_parameters,
+            base_string_parameters, signing_key, generate_nonce, generate_timestamp, percent_encode,
+            retry_refused};
+use super::response::CredentialsResponse;
+use super::quirks::Quirks;
+use super::signed_request::SignedRequest;
+use super::transport::{Response, Transport};
+use super::async_transport::{self, AsyncTransport, Pending};
+use ::crypto::SignatureMethod;
+use ::error::Error;
+use std::default::Default;
+
+#[derive(Clone)]
+pub struct Builder<'a> {
+    request_url         : &'a str,
+    consumer_key        : &'a str,
+    consumer_secret     : &'a str,
+    token               : &'a str,
+    token_secret        : &'a str,
+    verifier            : Option<&'a str>,
+    session_handle      : Option<&'a str>,
+    signature_method    : SignatureMethod,
+    quirks              : Quirks,
+    version             : Option<&'a str>,
+    realm               : Option<&'a str>,
+    parameters          : Vec<(&'a str, &'a str)>,
+    clock_offset        : i64,
+    retry               : bool,
+    legacy              : bool
+}
+
+#[derive(Clone)]
+pub struct TokenCredentials<'a> {
+    request_url         : &'a str,
+    consumer_key        : &'a str,
+    consumer_secret     : &'a str,
+    token               : &'a str,
+    token_secret        : &'a str,
+    verifier            : Option<&'a str>,
+    session_handle      : Option<&'a str>,
+    signature_method    : SignatureMethod,
+    quirks              : Quirks,
+    version             : Option<&'a str>,
+    realm               : Option<&'a str>,
+    parameters          : Vec<(&'a str, &'a str)>,
+    clock_offset        : i64,
+    retry               : bool,
+    legacy              : bool,
+    timestamp           : String,
+    nonce               : String,
+    signature           : String,
+}
+
+impl<'a> Builder<'a> {
+    /// `token` and `token_secret` are the temporary credentials, and `verifier` the
+    /// value received on the callback url
+    pub fn new(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+               token : &'a str, token_secret : &'a str, verifier : &'a str,
+               signature_method : SignatureMethod) -> Builder<'a> {
+        let mut builder = Builder::without_verifier(request_url, consumer_key, consumer_secret,
+                                                    token, token_secret, signature_method);
+        builder.verifier = Some(verifier);
+        builder
+    }
+
+    /// Builds a request sending no `oauth_verifier`, which is only correct for the
+    /// requests built by `renew`, `reverse_auth` and `legacy`
+    fn without_verifier(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                        token : &'a str, token_secret : &'a str,
+                        signature_method : SignatureMethod) -> Builder<'a> {
+        Builder {
+            request_url         : request_url,
+            consumer_key        : consumer_key,
+            consumer_secret     : consumer_secret,
+            token               : token,
+            token_secret        : token_secret,
+            verifier            : None,
+            session_handle      : None,
+            signature_method    : signature_method,
+            quirks              : Default::default(),
+            version             : None,
+            realm               : None,
+            parameters          : Vec::new(),
+            clock_offset        : 0,
+            retry               : false,
+            legacy              : false
+        }
+    }
+
+    /// Builds a renewal of expiring token credentials: `token` and `token_secret`
+    /// are the current token credentials, and `session_handle` the
+    /// `oauth_session_handle` issued with them
+    pub fn renew(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                 token : &'a str, token_secret : &'a str, session_handle : &'a str,
+                 signature_method : SignatureMethod) -> Builder<'a> {
+        let mut builder = Builder::without_verifier(request_url, consumer_key, consumer_secret, token,
+                                                    token_secret, signature_method);
+        builder.session_handle = Some(session_handle);
+        builder
+    }
+
+    /// Builds the second half of reverse auth, made by a device which holds the token
+    /// credentials of the resource owner under its own consumer. `target` is the
+    /// consumer key of the server the token credentials are issued to, and `parameters`
+    /// the unencoded response to its request made with
+    /// `temporary_credentials::Builder::reverse_auth`
+    pub fn reverse_auth(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                        token : &'a str, token_secret : &'a str, target : &'a str, parameters : &'a str,
+                        signature_method : SignatureMethod) -> Builder<'a> {
+        Builder::without_verifier(request_url, consumer_key, consumer_secret, token, token_secret,
+                                  signature_method)
+            .add_parameter("x_reverse_auth_parameters", parameters)
+            .add_parameter("x_reverse_auth_target", target)
+    }
+
+    /// Builds a request for token credentials to a provider speaking OAuth Core 1.0,
+    /// which issues no verifier, following `temporary_credentials::Builder::legacy`
+    pub fn legacy(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+                  token : &'a str, token_secret : &'a str, signature_method : SignatureMethod) -> Builder<'a> {
+        let mut builder = Builder::without_verifier(request_url, consumer_key, consumer_secret, token,
+                                                    token_secret, signature_method);
+        builder.legacy = true;
+        builder
+    }
+
+    pub fn use_version(mut self)-> Builder<'a> {
+        self.version = Some("1.0");
+        self
+    }
+
+    pub fn set_realm(mut self, realm : &'a str) -> Builder<'a> {
+        self.realm = Some(realm);
+        self
+    }
+
+    pub fn set_quirks(mut self, quirks : Quirks) -> Builder<'a> {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Adds `offset` seconds to the timestamp, see `Session::set_clock_offset`
+    pub fn set_clock_offset(mut self, offset : i64) -> Builder<'a> {
+        self.clock_offset = offset;
+        self
+    }
+
+    /// Signs and sends the request once more when the provider refuses it, see
+    /// `Session::set_retry`
+    pub fn set_retry(mut self, retry : bool) -> Builder<'a> {
+        self.retry = retry;
+        self
+    }
+
+    /// Adds a provider specific parameter, which is sent in the body and signed.
+    /// `value` must not be percent encoded
+    pub fn add_parameter(mut self, key : &'a str, value : &'a str) -> Builder<'a> {
+        self.parameters.push((key, value));
+        self
+    }
+
+    pub fn create(self) -> TokenCredentials<'a> {
+        TokenCredentials {
+            request_url         : self.request_url,
+            consumer_key        : self.consumer_key,
+            consumer_secret     : self.consumer_secret,
+            token               : self.token,
+            token_secret        : self.token_secret,
+            verifier            : self.verifier,
+            session_handle      : self.session_handle,
+            signature_method    : self.signature_method,
+            quirks              : self.quirks,
+            version             : self.version,
+            realm               : self.realm,
+            parameters          : self.parameters,
+            clock_offset        : self.clock_offset,
+            retry               : self.retry,
+            legacy              : self.legacy,
+            timestamp           : Default::default(),
+            nonce               : Default::default(),
+            signature           : Default::default(),
+        }
+    }
+}
+
+impl<'a> TokenCredentials<'a> {
+    /// Signs the request for token credentials without sending it
+    pub fn sign(&mut self) -> Result<SignedRequest, Error> {
+        let encoded : Vec<(String, String)> = self.parameters.iter()
+                                                  .map(|&(k, v)| (percent_encode(k), percent_encode(v)))
+                                                  .collect();
+        let data : Vec<(&str, &str)> = encoded.iter()
+                                              .map(|&(ref k, ref v)| (k.as_slice(), v.as_slice()))
+                                              .collect();
+
+        if self.legacy {
+            warn!("requesting token credentials from {} with OAuth 1.0, which is open to \
+                   session fixation; use OAuth 1.0a where the provider supports it", self.request_url);
+        }
+        self.timestamp = generate_timestamp(self.clock_offset, self.quirks.get_timestamp_unit());
+        self.nonce = try!(generate_nonce(self.quirks.get_nonce_format()));
+        let base_string = try!(self.get_base_string(HTTPMethod::POST, self.request_url, data.clone()));
+        self.signature = try!(self.signature_method.sign(base_string.clone(),
+                                                         signing_key(self.consumer_secret, self.token_secret)));
+        Ok(SignedRequest::with_quirks(HTTPMethod::POST, self.request_url, data, Transmission::Header,
+                                      self.realm, self.get_oauth_parameters(), base_string, &self.quirks))
+    }
+
+    /// Obtains token credentials, sending the request with `transport`
+    pub fn request<T: Transport>(&mut self, transport: &T) -> Result<CredentialsResponse, Error> {
+        parse_token_credentials(try!(self.send(transport)))
+    }
+
+    /// Like `request`, but sends the request with an `AsyncTransport` and returns
+    /// without waiting for the response
+    pub fn request_async<T: AsyncTransport>(&mut self, transport: &T)
+                                            -> Result<Pending<CredentialsResponse>, Error> {
+        let signed = try!(self.sign());
+        Ok(async_transport::send(transport, signed, parse_token_credentials))
+    }
+
+    /// Returns the clock offset, which is updated when the provider refuses the timestamp
+    pub fn get_clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+
+    /// Signs the request and sends it with `transport`, once more if the provider
+    /// refuses it and `Builder::set_retry` was used
+    fn send<T: Transport>(&mut self, transport: &T) -> Result<Response, Error> {
+        let signed = try!(self.sign());
+        let response = try!(transport.send(&signed));
+        if !retry_refused(&response, &mut self.clock_offset, self.retry) {
+            return Ok(response);
+        }
+        let signed = try!(self.sign());
+        transport.send(&signed)
+    }
+}
+
+/// Parses the response of the token credentials endpoint
+fn parse_token_credentials(response: Response) -> Result<CredentialsResponse, Error> {
+    try!(response.check_problem());
+    Ok(try!(CredentialsResponse::from_response(&response)))
+}
+
+impl<'a> AuthorizationHeader for TokenCredentials<'a> {
+    fn get_oauth_parameters(&self) -> Vec<(String, String)> {
+        let mut others = vec![("oauth_token", self.token)];
+        match self.verifier {
+            Some(verifier) => others.push(("oauth_verifier", verifier)),
+            None => ()
+        };
+        match self.session_handle {
+            Some(handle) => others.push(("oauth_session_handle", handle)),
+            None => ()
+        };
+        protocol_parameters(self.consumer_key, self.signature_method, self.nonce.as_slice(),
+                            self.timestamp.as_slice(), self.signature.as_slice(), self.version, others)
+    }
+
+    fn get_realm(&self) -> Option<&str> {
+        self.realm
+    }
+
+    fn get_quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+}
+
+
+impl <'a> super::BaseString for TokenCredentials<'a> {
+    fn get_self_paramaters(&self) ->  Vec<String>{
+        base_string_parameters(self.get_oauth_parameters())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Builder;
+    use oauth1::client::{AuthorizationHeader, HTTPMethod};
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::transport::Response;
+    use oauth1::client::async_transport::Reply;
+    use oauth1::client::problem::ProblemKind;
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    #[test]
+    /// Verifies the header sent to the token credentials endpoint, using the
+    /// example from [RFC 5849 &sect; 2.3](http://tools.ietf.org/html/rfc5849#section-2.3)
+    fn token_credentials_header_test() {
+        let mut creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                     "hfdp7dh39dks9884", SignatureMethod::HMACSHA1)
+                            .set_realm("Photos")
+                            .create();
+        creds.timestamp = String::from_str("137131201");
+        creds.nonce = String::from_str("walatlh");
+        creds.signature = String::from_str("gKgrFCywp7rO0OXSjdot/IHF7IU=");
+
+        assert_eq!(creds.get_header(),
+                   "OAuth realm=\"Photos\", oauth_consumer_key=\"dpf43f3p2l4k3l03\", \
+                    oauth_nonce=\"walatlh\", oauth_signature=\"gKgrFCywp7rO0OXSjdot%2FIHF7IU%3D\", \
+                    oauth_signature_method=\"HMAC-SHA1\", oauth_timestamp=\"137131201\", \
+                    oauth_token=\"hh5s93j4hdidpola\", oauth_verifier=\"hfdp7dh39dks9884\"".to_string());
+    }
+
+    #[test]
+    /// Exchange from [RFC 5849 &sect; 2.3](http://tools.ietf.org/html/rfc5849#section-2.3)
+    fn token_credentials_exchange_test() {
+        let mut creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                     "hfdp7dh39dks9884", SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(*req.get_method(), HTTPMethod::POST);
+            assert!(req.get_header("Authorization").unwrap().contains("oauth_verifier=\"hfdp7dh39dks9884\""));
+            let body = b"oauth_token=nnch734d00sl2jdk&oauth_token_secret=pfkkdhi9sl3r4s00";
+            Ok(Response::new(200, vec![("Content-Type".to_string(),
+                                        "application/x-www-form-urlencoded".to_string())], body.to_vec()))
+        };
+        let resp = creds.request(&transport).unwrap();
+        assert_eq!(resp.get_token(), "nnch734d00sl2jdk");
+        assert_eq!(resp.get_token_secret(), "pfkkdhi9sl3r4s00");
+    }
+
+    #[test]
+    fn token_credentials_empty_verifier_test() {
+        // an empty verifier is sent as is, rather than mistaken for a request without one
+        let creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03", "kd94hf93k423kf44",
+                                 "hh5s93j4hdidpola", "hdhd0244k9j7ao03", "", SignatureMethod::HMACSHA1)
+                        .create();
+        assert!(creds.get_header().contains("oauth_verifier=\"\""));
+    }
+
+    #[test]
+    fn token_credentials_renew_test() {
+        let mut creds = Builder::renew("https://api.login.yahoo.com/oauth/v2/get_token", "dpf43f3p2l4k3l03",
+                                       "kd94hf93k423kf44", "nnch734d00sl2jdk", "pfkkdhi9sl3r4s00",
+                                       "AJ9m8fo.ZXyd", SignatureMethod::PLAINTEXT)
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            let header = req.get_header("Authorization").unwrap();
+            assert!(header.contains("oauth_session_handle=\"AJ9m8fo.ZXyd\""));
+            assert!(header.contains("oauth_token=\"nnch734d00sl2jdk\""));
+            assert!(!header.contains("oauth_verifier"));
+            assert_eq!(req.get_signature(), "kd94hf93k423kf44&pfkkdhi9sl3r4s00");
+            let body = b"oauth_token=renewed&oauth_token_secret=renewed_secret&oauth_expires_in=3600\
+                         &oauth_session_handle=AJ9m8fo.ZXyd";
+            Ok(Response::new(200, vec![], body.to_vec()))
+        };
+        let resp = creds.request(&transport).unwrap();
+        assert_eq!(resp.get_token(), "renewed");
+        assert_eq!(resp.get_expires_in(), Some(3600));
+        assert_eq!(resp.get_session_handle(), Some("AJ9m8fo.ZXyd"));
+    }
+
+    #[test]
+    fn token_credentials_legacy_test() {
+        let mut creds = Builder::legacy("https://legacy.example.com/access_token", "dpf43f3p2l4k3l03",
+                                        "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                        SignatureMethod::PLAINTEXT)
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            let header = req.get_header("Authorization").unwrap();
+            assert!(header.contains("oauth_token=\"hh5s93j4hdidpola\""));
+            assert!(!header.contains("oauth_verifier"));
+            let body = b"oauth_token=nnch734d00sl2jdk&oauth_token_secret=pfkkdhi9sl3r4s00";
+            Ok(Response::new(200, vec![], body.to_vec()))
+        };
+        assert_eq!(creds.request(&transport).unwrap().get_token(), "nnch734d00sl2jdk");
+    }
+
+    #[test]
+    fn token_credentials_reverse_auth_test() {
+        let parameters = "OAuth oauth_nonce=\"abc\", oauth_consumer_key=\"server_key\"";
+        let mut creds = Builder::reverse_auth("https://api.twitter.com/oauth/access_token", "device_key",
+                                              "device_secret", "token", "token_secret", "server_key",
+                                              parameters, SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(req.get_body(), Some("x_reverse_auth_parameters=OAuth%20oauth_nonce%3D%22abc%22%2C\
+                                             %20oauth_consumer_key%3D%22server_key%22&\
+                                             x_reverse_auth_target=server_key"));
+            let header = req.get_header("Authorization").unwrap();
+            assert!(header.contains("oauth_consumer_key=\"device_key\""));
+            assert!(header.contains("oauth_token=\"token\""));
+            assert!(!header.contains("oauth_verifier"));
+            let expected = SignatureMethod::HMACSHA1.sign(req.get_base_string().to_string(),
+                                                          String::from_str("device_secret&token_secret")).unwrap();
+            assert_eq!(req.get_signature(), expected.as_slice());
+            let body = b"oauth_token=server_token&oauth_token_secret=server_secret&screen_name=twitterapi";
+            Ok(Response::new(200, vec![], body.to_vec()))
+        };
+        let resp = creds.request(&transport).unwrap();
+        assert_eq!(resp.get_token(), "server_token");
+        assert_eq!(resp.get("screen_name"), Some("twitterapi"));
+    }
+
+    #[test]
+    fn token_credentials_problem_test() {
+        let mut creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                     "hfdp7dh39dks9884", SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |_: &SignedRequest| -> Result<Response, Error> {
+            Ok(Response::new(401, vec![("WWW-Authenticate".to_string(),
+                                        "OAuth oauth_problem=\"token_expired\"".to_string())], vec![]))
+        };
+        match creds.request(&transport) {
+            Err(Error::Problem(ref p)) => assert_eq!(*p.get_kind(), ProblemKind::TokenExpired),
+            _ => panic!("expected Error::Problem")
+        }
+    }
+
+    #[test]
+    fn token_credentials_timestamp_refused_test() {
+        use oauth1::client::time::get_time;
+        let server_time = get_time().sec + 600;
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            let timestamp : i64 = req.get_timestamp().unwrap().parse().unwrap();
+            if (timestamp - server_time).abs() > 5 {
+                let header = format!("OAuth oauth_problem=\"timestamp_refused\", \
+                                      oauth_acceptable_timestamps=\"{}-{}\"", server_time - 300, server_time + 300);
+                Ok(Response::new(401, vec![("WWW-Authenticate".to_string(), header)], vec![]))
+            } else {
+                Ok(Response::new(200, vec![], b"oauth_token=nnch734d00sl2jdk&oauth_token_secret=pfkkdhi9sl3r4s00".to_vec()))
+            }
+        };
+        let mut creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                     "hfdp7dh39dks9884", SignatureMethod::HMACSHA1)
+                            .set_retry(true)
+                            .create();
+        assert_eq!(creds.request(&transport).unwrap().get_token(), "nnch734d00sl2jdk");
+        assert!((creds.get_clock_offset() - 600).abs() <= 5);
+
+        // an offset learned earlier is used from the first request
+        let mut creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                     "hfdp7dh39dks9884", SignatureMethod::HMACSHA1)
+                            .set_clock_offset(600)
+                            .create();
+        assert_eq!(creds.request(&transport).unwrap().get_token(), "nnch734d00sl2jdk");
+    }
+
+    #[test]
+    fn token_credentials_async_exchange_test() {
+        use std::thread;
+        let mut creds = Builder::new("https://photos.example.net/token", "dpf43f3p2l4k3l03",
+                                     "kd94hf93k423kf44", "hh5s93j4hdidpola", "hdhd0244k9j7ao03",
+                                     "hfdp7dh39dks9884", SignatureMethod::HMACSHA1)
+                            .create();
+        let transport = |_: SignedRequest, reply: Reply| {
+            thread::spawn(move || {
+                let body = b"oauth_token=nnch734d00sl2jdk&oauth_token_secret=pfkkdhi9sl3r4s00";
+                reply.complete(Ok(Response::new(200, vec![], body.to_vec())))
+            });
+        };
+        let resp = creds.request_async(&transport).unwrap().wait().unwrap();
+        assert_eq!(resp.get_token(), "nnch734d00sl2jdk");
+    }
+}
@@ -0,0 +1,138 @@
+//! Sends signed requests with any http library.
+//!
+//! A `Transport` receives a `SignedRequest` and returns a `Response`. Any closure
+//! or function taking a `&SignedRequest` and returning `Result<Response, Error>`
+//! is a transport, so it can capture a client, a connection pool or configuration.
+//!
+//!# Example
+//!
+//!```
+//! use rust_oauth::Error;
+//! use rust_oauth::oauth1::client::signed_request::SignedRequest;
+//! use rust_oauth::oauth1::client::transport::{Response, Transport};
+//!
+//! let user_agent = "example/1.0";
+//! let transport = |req: &SignedRequest| -> Result<Response, Error> {
+//!     println!("{} {} ({})", req.get_method(), req.get_url(), user_agent);
+//!     Ok(Response::new(200, vec![], Vec::new()))
+//! };
+//!```
+
+use oauth1::client::problem::Problem;
+use oauth1::client::signed_request::SignedRequest;
+use error::Error;
+use std::str;
+
+/// Response to a signed request, independent of any http library
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    status : u32,
+    headers : Vec<(String, String)>,
+    body : Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u32, headers: Vec<(String, String)>, body: Vec<u8>) -> Response {
+        Response {
+            status : status,
+            headers : headers,
+            body : body,
+        }
+    }
+
+    /// Returns the http status code
+    pub fn get_status(&self) -> u32 {
+        self.status
+    }
+
+    /// Whether the status code is in the `2xx` range
+    pub fn is_success(&self) -> bool {
+        self.status >= 200 && self.status < 300
+    }
+
+    /// Returns every header received, as `(name, value)` pairs
+    pub fn get_headers(&self) -> &[(String, String)] {
+        self.headers.as_slice()
+    }
+
+    /// Returns the first value of the header `name`, compared case-insensitively
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        use std::ascii::AsciiExt;
+        self.headers.iter()
+                    .find(|&&(ref k, _)| k.as_slice().eq_ignore_ascii_case(name))
+                    .map(|&(_, ref v)| v.as_slice())
+    }
+
+    /// Returns the value of the `Content-Type` header
+    pub fn get_content_type(&self) -> Option<&str> {
+        self.get_header("Content-Type")
+    }
+
+    pub fn get_body(&self) -> &[u8] {
+        self.body.as_slice()
+    }
+
+    /// Returns the body as a string, if it is valid UTF-8
+    pub fn get_body_str(&self) -> Option<&str> {
+        str::from_utf8(self.body.as_slice()).ok()
+    }
+
+    /// Returns the problem reported by the provider when the request failed, if any
+    pub fn get_problem(&self) -> Option<Problem> {
+        if self.is_success() {
+            None
+        } else {
+            Problem::from_response(self)
+        }
+    }
+
+    /// Fails with `Error::Problem` if the provider reported a problem
+    pub fn check_problem(&self) -> Result<(), Error> {
+        match self.get_problem() {
+            Some(problem) => Err(Error::Problem(problem)),
+            None => Ok(())
+        }
+    }
+}
+
+/// Sends a signed request over http
+pub trait Transport {
+    /// Sends `request` exactly as signed: to its url, with its method, headers and body.
+    /// Failures of the underlying http library are reported as `Error::Transport`
+    fn send(&self, request: &SignedRequest) -> Result<Response, Error>;
+}
+
+impl<F> Transport for F where F: Fn(&SignedRequest) -> Result<Response, Error> {
+    fn send(&self, request: &SignedRequest) -> Result<Response, Error> {
+        (*self)(request)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Response, Transport};
+    use oauth1::client::{HTTPMethod, Transmission};
+    use oauth1::client::signed_request::SignedRequest;
+    use error::Error;
+
+    fn echo(request: &SignedRequest) -> Result<Response, Error> {
+        Ok(Response::new(200, vec![("Content-Type".to_string(), "text/plain".to_string())],
+                         request.get_url().as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn transport_from_fn_and_closure() {
+        let req = SignedRequest::new(HTTPMethod::GET, "http://example.com/", vec![],
+                                     Transmission::Header, None, vec![], String::new());
+        let resp = echo.send(&req).unwrap();
+        assert_eq!(resp.get_body_str(), Some("http://example.com/"));
+        assert_eq!(resp.get_content_type(), Some("text/plain"));
+
+        let status = 404;
+        let closure = |_: &SignedRequest| Ok(Response::new(status, vec![], Vec::new()));
+        let resp = closure.send(&req).unwrap();
+        assert_eq!(resp.get_status(), 404);
+        assert!(!resp.is_success());
+    }
+}
@@ -0,0 +1,269 @@
+//! Request for token credentials with xAuth, which exchanges the resource owner's
+//! username and password for token credentials in a single signed request, without
+//! a callback or verifier. Only providers which allowed the client to use xAuth
+//! accept it.
+//!
+//! The `x_auth_username`, `x_auth_password` and `x_auth_mode=client_auth`
+//! parameters are sent in the body, and signed along with the OAuth parameters.
+
+use super::{HTTPMethod, AuthorizationHeader, BaseString, Transmission, protocol_parameters,
+            base_string_parameters, signing_key, generate_nonce, generate_timestamp, percent_encode,
+            retry_refused};
+use super::response::CredentialsResponse;
+use super::quirks::Quirks;
+use super::signed_request::SignedRequest;
+use super::transport::{Response, Transport};
+use super::async_transport::{self, AsyncTransport, Pending};
+use ::crypto::SignatureMethod;
+use ::error::Error;
+use std::default::Default;
+
+#[derive(Clone)]
+pub struct Builder<'a> {
+    request_url         : &'a str,
+    consumer_key        : &'a str,
+    consumer_secret     : &'a str,
+    username            : &'a str,
+    password            : &'a str,
+    signature_method    : SignatureMethod,
+    quirks              : Quirks,
+    version             : Option<&'a str>,
+    realm               : Option<&'a str>,
+    clock_offset        : i64,
+    retry               : bool
+}
+
+#[derive(Clone)]
+pub struct XAuth<'a> {
+    request_url         : &'a str,
+    consumer_key        : &'a str,
+    consumer_secret     : &'a str,
+    username            : &'a str,
+    password            : &'a str,
+    signature_method    : SignatureMethod,
+    quirks              : Quirks,
+    version             : Option<&'a str>,
+    realm               : Option<&'a str>,
+    clock_offset        : i64,
+    retry               : bool,
+    timestamp           : String,
+    nonce               : String,
+    signature           : String,
+}
+
+impl<'a> Builder<'a> {
+    /// `request_url` is the provider's token credentials endpoint, and `username` and
+    /// `password` the unencoded credentials of the resource owner
+    pub fn new(request_url : &'a str, consumer_key : &'a str, consumer_secret : &'a str,
+               username : &'a str, password : &'a str, signature_method : SignatureMethod) -> Builder<'a> {
+        Builder {
+            request_url         : request_url,
+            consumer_key        : consumer_key,
+            consumer_secret     : consumer_secret,
+            username            : username,
+            password            : password,
+            signature_method    : signature_method,
+            quirks              : Default::default(),
+            version             : None,
+            realm               : None,
+            clock_offset        : 0,
+            retry               : false
+        }
+    }
+
+    pub fn use_version(mut self)-> Builder<'a> {
+        self.version = Some("1.0");
+        self
+    }
+
+    pub fn set_realm(mut self, realm : &'a str) -> Builder<'a> {
+        self.realm = Some(realm);
+        self
+    }
+
+    pub fn set_quirks(mut self, quirks : Quirks) -> Builder<'a> {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Adds `offset` seconds to the timestamp, see `Session::set_clock_offset`
+    pub fn set_clock_offset(mut self, offset : i64) -> Builder<'a> {
+        self.clock_offset = offset;
+        self
+    }
+
+    /// Signs and sends the request once more when the provider refuses it, see
+    /// `Session::set_retry`
+    pub fn set_retry(mut self, retry : bool) -> Builder<'a> {
+        self.retry = retry;
+        self
+    }
+
+    pub fn create(self) -> XAuth<'a> {
+        XAuth {
+            request_url         : self.request_url,
+            consumer_key        : self.consumer_key,
+            consumer_secret     : self.consumer_secret,
+            username            : self.username,
+            password            : self.password,
+            signature_method    : self.signature_method,
+            quirks              : self.quirks,
+            version             : self.version,
+            realm               : self.realm,
+            clock_offset        : self.clock_offset,
+            retry               : self.retry,
+            timestamp           : Default::default(),
+            nonce               : Default::default(),
+            signature           : Default::default(),
+        }
+    }
+}
+
+impl<'a> XAuth<'a> {
+    /// Signs the xAuth request without sending it
+    pub fn sign(&mut self) -> Result<SignedRequest, Error> {
+        let username = percent_encode(self.username);
+        let password = percent_encode(self.password);
+        let data = vec![("x_auth_mode", "client_auth"),
+                        ("x_auth_password", password.as_slice()),
+                        ("x_auth_username", username.as_slice())];
+
+        self.timestamp = generate_timestamp(self.clock_offset, self.quirks.get_timestamp_unit());
+        self.nonce = try!(generate_nonce(self.quirks.get_nonce_format()));
+        let base_string = try!(self.get_base_string(HTTPMethod::POST, self.request_url, data.clone()));
+        self.signature = try!(self.signature_method.sign(base_string.clone(),
+                                                         signing_key(self.consumer_secret, "")));
+        Ok(SignedRequest::with_quirks(HTTPMethod::POST, self.request_url, data, Transmission::Header,
+                                      self.realm, self.get_oauth_parameters(), base_string, &self.quirks))
+    }
+
+    /// Obtains token credentials, sending the request with `transport`
+    pub fn request<T: Transport>(&mut self, transport: &T) -> Result<CredentialsResponse, Error> {
+        parse_xauth_credentials(try!(self.send(transport)))
+    }
+
+    /// Like `request`, but sends the request with an `AsyncTransport` and returns
+    /// without waiting for the response
+    pub fn request_async<T: AsyncTransport>(&mut self, transport: &T)
+                                            -> Result<Pending<CredentialsResponse>, Error> {
+        let signed = try!(self.sign());
+        Ok(async_transport::send(transport, signed, parse_xauth_credentials))
+    }
+
+    /// Returns the clock offset, which is updated when the provider refuses the timestamp
+    pub fn get_clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+
+    /// Signs the request and sends it with `transport`, once more if the provider
+    /// refuses it and `Builder::set_retry` was used
+    fn send<T: Transport>(&mut self, transport: &T) -> Result<Response, Error> {
+        let signed = try!(self.sign());
+        let response = try!(transport.send(&signed));
+        if !retry_refused(&response, &mut self.clock_offset, self.retry) {
+            return Ok(response);
+        }
+        let signed = try!(self.sign());
+        transport.send(&signed)
+    }
+}
+
+/// Parses the token credentials returned for an xAuth request
+fn parse_xauth_credentials(response: Response) -> Result<CredentialsResponse, Error> {
+    try!(response.check_problem());
+    Ok(try!(CredentialsResponse::from_response(&response)))
+}
+
+impl<'a> AuthorizationHeader for XAuth<'a> {
+    fn get_oauth_parameters(&self) -> Vec<(String, String)> {
+        protocol_parameters(self.consumer_key, self.signature_method, self.nonce.as_slice(),
+                            self.timestamp.as_slice(), self.signature.as_slice(), self.version, vec![])
+    }
+
+    fn get_realm(&self) -> Option<&str> {
+        self.realm
+    }
+
+    fn get_quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+}
+
+
+impl <'a> super::BaseString for XAuth<'a> {
+    fn get_self_paramaters(&self) ->  Vec<String>{
+        base_string_parameters(self.get_oauth_parameters())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Builder;
+    use oauth1::client::{AuthorizationHeader, HTTPMethod};
+    use oauth1::client::signed_request::SignedRequest;
+    use oauth1::client::transport::Response;
+    use crypto::SignatureMethod;
+    use error::Error;
+
+    #[test]
+    /// Base string of a signed xAuth request, whose credentials are percent encoded in
+    /// the body and encoded once more in the base string
+    fn xauth_base_string_test() {
+        let mut xauth = Builder::new("https://api.twitter.com/oauth/access_token", "JvyS7DO2qd6NNTsXJ4E7zA",
+                                     "9z6157pUbOBqtbm0A0q4r29Y2EYzIHlUwbF4Cl9c", "user@example.com",
+                                     "p@ss word", SignatureMethod::HMACSHA1)
+                          .use_version()
+                          .create();
+        let signed = xauth.sign().unwrap();
+        let expected = format!("POST&https%3A%2F%2Fapi.twitter.com%2Foauth%2Faccess_token\
+                                &oauth_consumer_key%3DJvyS7DO2qd6NNTsXJ4E7zA%26oauth_nonce%3D{}\
+                                %26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D{}\
+                                %26oauth_version%3D1.0%26x_auth_mode%3Dclient_auth\
+                                %26x_auth_password%3Dp%2540ss%2520word\
+                                %26x_auth_username%3Duser%2540example.com",
+                               signed.get_nonce().unwrap(), signed.get_timestamp().unwrap());
+        assert_eq!(signed.get_base_string(), expected.as_slice());
+        let signature = SignatureMethod::HMACSHA1.sign(expected,
+                                                       String::from_str("9z6157pUbOBqtbm0A0q4r29Y2EYzIHlUwbF4Cl9c&"))
+                                                 .unwrap();
+        assert_eq!(signed.get_signature(), signature.as_slice());
+    }
+
+    #[test]
+    fn xauth_exchange_test() {
+        let mut xauth = Builder::new("https://example.com/access_token", "key", "secret",
+                                     "user@example.com", "p@ss word", SignatureMethod::PLAINTEXT)
+                          .create();
+        let transport = |req: &SignedRequest| -> Result<Response, Error> {
+            assert_eq!(*req.get_method(), HTTPMethod::POST);
+            assert_eq!(req.get_body(), Some("x_auth_mode=client_auth&x_auth_password=p%40ss%20word&\
+                                             x_auth_username=user%40example.com"));
+            let header = req.get_header("Authorization").unwrap();
+            assert!(!header.contains("oauth_token"));
+            assert!(!header.contains("oauth_callback"));
+            assert_eq!(req.get_signature(), "secret&");
+            let body = b"oauth_token=abc&oauth_token_secret=def&x_auth_expires=0";
+            Ok(Response::new(200, vec![], body.to_vec()))
+        };
+        let resp = xauth.request(&transport).unwrap();
+        assert_eq!(resp.get_token(), "abc");
+        assert_eq!(resp.get_token_secret(), "def");
+        assert_eq!(resp.get("x_auth_expires"), Some("0"));
+    }
+
+    #[test]
+    fn xauth_header_test() {
+        let mut xauth = Builder::new("https://example.com/access_token", "key", "secret",
+                                     "user", "password", SignatureMethod::HMACSHA1)
+                          .set_realm("Example")
+                          .create();
+        xauth.timestamp = String::from_str("137131200");
+        xauth.nonce = String::from_str("wIjqoS");
+        xauth.signature = String::from_str("abc=");
+        assert_eq!(xauth.get_header(),
+                   "OAuth realm=\"Example\", oauth_consumer_key=\"key\", oauth_nonce=\"wIjqoS\", \
+                    oauth_signature=\"abc%3D\", oauth_signature_method=\"HMAC-SHA1\", \
+                    oauth_timestamp=\"137131200\"".to_string());
+    }
+}
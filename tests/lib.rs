@@ -1,29 +1,14 @@
+#![cfg(feature = "curl")]
 #![feature(core)]
 extern crate rust_oauth;
-extern crate curl;
 
 use rust_oauth::crypto::SignatureMethod;
 use rust_oauth::oauth1::client::session::Session;
-use rust_oauth::oauth1::client::{HTTPMethod, AuthorizationHeader, concat};
-
-use curl::http;
-fn rust_curl_callback(session: Session<http::response::Response>, method: HTTPMethod,
-                      url: &str, data: Vec<(&str, &str)>) -> http::response::Response {
-    let to_pair = | (key, value) : (&str, &str) | -> String { format!("{}={}", key, value) };
-    let header = session.get_header();
-    println!("header:\n\n{}\n\n", header);
-    // combine all data into url format (add equals signs and )
-    let url_data = concat(data.into_iter().map(to_pair).collect::<Vec<String>>().as_slice(), "&");
-    println!("url: \n\n{}\n\n", url_data);
-
-    http::handle()
-        .get(format!("{}?{}", url, url_data)).header("Authorization", header.as_slice())
-        .exec().unwrap()
-}
+use rust_oauth::oauth1::client::curl_transport::CurlTransport;
+use rust_oauth::oauth1::client::HTTPMethod;
 
 #[test]
 fn twitter_api_rustcurl() {
-    use std::str;
     let mut s = Session::new
                     (
                         "k0azC44q2c0DgF7ua9YZ6Q", // consumer_key
@@ -31,11 +16,10 @@ fn twitter_api_rustcurl() {
                         "119544186-6YZKqkECA9Z0bxq9bA1vzzG7tfPotCml4oTySkzj", // token
                         "zvNmU9daj9V00118H9KQBozQQsZt4pyLQcZdc", // token_secret
                         SignatureMethod::HMACSHA1, // signature_method
-                        rust_curl_callback,
                     );
-    let resp = s.request(HTTPMethod::GET, "https://api.twitter.com/1.1/statuses/user_timeline.json",
-                         vec![("screen_name", "twitterapi"), ("count", "2")]);
-    let out = str::from_utf8(resp.get_body());
-    println!("body={}\ncode={}\n", out.unwrap(), resp.get_code());
-    assert_eq!(resp.get_code(), 200);
+    let resp = s.request(&CurlTransport::new(), HTTPMethod::GET,
+                         "https://api.twitter.com/1.1/statuses/user_timeline.json",
+                         vec![("screen_name", "twitterapi"), ("count", "2")]).unwrap();
+    println!("body={}\ncode={}\n", resp.get_body_str().unwrap(), resp.get_status());
+    assert_eq!(resp.get_status(), 200);
 }
@@ -0,0 +1,156 @@
+//! Runs the built-in transports against a loopback server which echoes back the
+//! request it received
+#![cfg(any(feature = "curl", feature = "hyper"))]
+#![feature(core, io, net, std_misc)]
+extern crate rust_oauth;
+
+use std::ascii::AsciiExt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use rust_oauth::crypto::SignatureMethod;
+use rust_oauth::oauth1::client::{HTTPMethod, Transmission};
+use rust_oauth::oauth1::client::session::Session;
+use rust_oauth::oauth1::client::transport::Transport;
+
+/// Accepts a single connection on a random port, and answers with a body made of
+/// the request line, the `Authorization` and `Content-Type` headers and the body
+/// received, one per line. Returns the base url of the server
+fn echo_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.socket_addr().unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut authorization = String::new();
+        let mut content_type = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            let mut split = line.splitn(1, ':');
+            let name = split.next().unwrap().trim().to_ascii_lowercase();
+            let value = split.next().unwrap_or("").trim().to_string();
+            match name.as_slice() {
+                "authorization" => authorization = value,
+                "content-type" => content_type = value,
+                "content-length" => content_length = value.parse().unwrap(),
+                _ => ()
+            }
+        }
+        let mut body = Vec::new();
+        while body.len() < content_length {
+            let mut buf = [0u8; 512];
+            let n = reader.read(&mut buf).unwrap();
+            body.push_all(&buf[..n]);
+        }
+
+        let echo = format!("{}\n{}\n{}\n{}", request_line.trim(), authorization, content_type,
+                           String::from_utf8(body).unwrap());
+        write!(&mut stream, "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+               echo.len(), echo).unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+fn session() -> Session<'static> {
+    Session::new("9djdj82h48djs9d2", "j49sk3j29djd", "kkk9d7dh3k39sjv7", "dh893hdasih9",
+                 SignatureMethod::HMACSHA1)
+}
+
+/// Sends a request with `transport` and returns the lines echoed by the server
+fn echo<T: Transport>(transport: &T, s: &mut Session, method: HTTPMethod,
+                      path: &str, data: Vec<(&str, &str)>) -> Vec<String> {
+    let url = format!("{}{}", echo_server(), path);
+    let resp = s.request(transport, method, url.as_slice(), data).unwrap();
+    assert_eq!(resp.get_status(), 200);
+    resp.get_body_str().unwrap().split('\n').map(|l| l.to_string()).collect()
+}
+
+fn check_get<T: Transport>(transport: &T) {
+    let lines = echo(transport, &mut session(), HTTPMethod::GET, "/get?a=1", vec![("b", "2%203")]);
+    assert_eq!(lines[0], "GET /get?a=1&b=2%203 HTTP/1.1".to_string());
+    assert!(lines[1].starts_with("OAuth oauth_consumer_key=\"9djdj82h48djs9d2\""));
+}
+
+fn check_head<T: Transport>(transport: &T) {
+    let url = format!("{}/head", echo_server());
+    let resp = session().request(transport, HTTPMethod::HEAD, url.as_slice(), vec![]).unwrap();
+    assert_eq!(resp.get_status(), 200);
+    assert_eq!(resp.get_body(), b"");
+}
+
+fn check_delete<T: Transport>(transport: &T) {
+    let lines = echo(transport, &mut session(), HTTPMethod::DELETE, "/delete", vec![("id", "7")]);
+    assert_eq!(lines[0], "DELETE /delete?id=7 HTTP/1.1".to_string());
+}
+
+fn check_post_form<T: Transport>(transport: &T) {
+    let lines = echo(transport, &mut session(), HTTPMethod::POST, "/post", vec![("status", "a%20b")]);
+    assert_eq!(lines[0], "POST /post HTTP/1.1".to_string());
+    assert!(lines[1].starts_with("OAuth "));
+    assert_eq!(lines[2], "application/x-www-form-urlencoded".to_string());
+    assert_eq!(lines[3], "status=a%20b".to_string());
+}
+
+fn check_put_body_transmission<T: Transport>(transport: &T) {
+    let mut s = session().set_transmission(Transmission::Body);
+    let lines = echo(transport, &mut s, HTTPMethod::PUT, "/put", vec![("c", "d")]);
+    assert_eq!(lines[0], "PUT /put HTTP/1.1".to_string());
+    assert_eq!(lines[1], "".to_string());
+    assert!(lines[3].starts_with("c=d&oauth_consumer_key=9djdj82h48djs9d2&"));
+}
+
+fn check_patch<T: Transport>(transport: &T) {
+    let lines = echo(transport, &mut session(), HTTPMethod::PATCH, "/patch", vec![("e", "f")]);
+    assert_eq!(lines[0], "PATCH /patch HTTP/1.1".to_string());
+    assert!(lines[1].starts_with("OAuth "));
+    assert_eq!(lines[3], "e=f".to_string());
+}
+
+fn check_options<T: Transport>(transport: &T) {
+    let lines = echo(transport, &mut session(), HTTPMethod::OPTIONS, "/options", vec![]);
+    assert_eq!(lines[0], "OPTIONS /options HTTP/1.1".to_string());
+    assert!(lines[1].starts_with("OAuth "));
+}
+
+fn check_query_transmission<T: Transport>(transport: &T) {
+    let mut s = session().set_transmission(Transmission::Query);
+    let lines = echo(transport, &mut s, HTTPMethod::GET, "/query", vec![]);
+    assert!(lines[0].starts_with("GET /query?oauth_consumer_key=9djdj82h48djs9d2&"));
+    assert_eq!(lines[1], "".to_string());
+}
+
+fn check_all<T: Transport>(transport: &T) {
+    check_get(transport);
+    check_head(transport);
+    check_delete(transport);
+    check_post_form(transport);
+    check_put_body_transmission(transport);
+    check_patch(transport);
+    check_options(transport);
+    check_query_transmission(transport);
+}
+
+#[cfg(feature = "curl")]
+#[test]
+fn curl_transport_loopback() {
+    use rust_oauth::oauth1::client::curl_transport::CurlTransport;
+    check_all(&CurlTransport::new());
+}
+
+#[cfg(feature = "hyper")]
+#[test]
+fn hyper_transport_loopback() {
+    use rust_oauth::oauth1::client::hyper_transport::HyperTransport;
+    check_all(&HyperTransport::new());
+}